@@ -0,0 +1,83 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Sample,
+    hash::hash_types::HashOut,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    aggregate::{aggregate_proofs, slot_public_inputs, SLOT_LEN},
+    compress::proof_sizes,
+    recursion::ProofTuple,
+    schnorr::{self, SchnorrCircuit},
+    statement::{Statement, StatementCircuit},
+    xmss::{XmssCircuit, XmssSigner},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // Three unrelated statements: a Schnorr signature, an XMSS signature and a * b = c.
+    // Each has its own circuit, of its own degree, and one proof verifies all three.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    let tmp = Instant::now();
+    let mut children = Vec::new();
+
+    let mut rng = rand::thread_rng();
+    let (sk, pk) = schnorr::keygen(&mut rng);
+    let msg = HashOut::<F>::rand();
+    let sig = schnorr::sign(msg, &sk, &mut rng);
+    let circuit = SchnorrCircuit::<F, C, D>::new(ProofMode::Standard);
+    let proof = circuit.prove(msg, &sig, &pk)?;
+    children.push(ProofTuple::leaf(proof, &circuit.data));
+
+    let mut signer = XmssSigner::new(HashOut::<F>::rand(), 4);
+    let root = signer.public_key();
+    let sig = signer.sign(msg)?;
+    let circuit = XmssCircuit::<F, C, D>::new(4, ProofMode::Standard);
+    let proof = circuit.prove(msg, &sig, root)?;
+    children.push(ProofTuple::leaf(proof, &circuit.data));
+
+    let statement = Statement::from_json(r#"{ "statement": "a * b = c", "public": ["c"] }"#)?;
+    let circuit = StatementCircuit::<F, C, D>::new(statement, ProofMode::Standard);
+    let proof = circuit.prove(r#"{ "a": 17, "b": 19, "c": 323 }"#)?;
+    children.push(ProofTuple::leaf(proof, &circuit.data));
+
+    for (name, child) in ["Schnorr", "XMSS", "a * b = c"].iter().zip(children.iter()) {
+        println!(
+            "{}: degree {}, {} public inputs",
+            name,
+            child.cd.degree(),
+            child.cd.num_public_inputs
+        );
+    }
+    println!("Child proofs took {}ms", tmp.elapsed().as_millis());
+
+    let tmp = Instant::now();
+    let aggregate = aggregate_proofs(&children, proof_mode)?;
+    println!("Aggregation took {}ms", tmp.elapsed().as_millis());
+    println!("Aggregate degree: {}", aggregate.cd.degree());
+
+    let expected = children
+        .iter()
+        .flat_map(slot_public_inputs)
+        .collect::<Vec<_>>();
+    ensure!(
+        aggregate.statement() == expected,
+        "unexpected public inputs"
+    );
+    ensure!(aggregate.statement().len() == SLOT_LEN * children.len());
+    ensure!(aggregate.depth == 1 && aggregate.leaves() == children.len() as u64);
+
+    let data = aggregate.verifier_data();
+    println!("Proof size: {}", proof_sizes(&aggregate.proof, &data)?);
+    data.verify(aggregate.proof.clone())?;
+    println!("Verified a {} proof", aggregate.mode());
+    println!("Schnorr, XMSS and a * b = c proofs verified together");
+
+    Ok(())
+}
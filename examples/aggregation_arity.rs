@@ -0,0 +1,123 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{extension::Extendable, types::Field},
+    hash::{hash_types::RichField, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+    plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    merkle::zero_hash,
+    merkle_subset::SubsetCircuit,
+    pow5::Pow5Circuit,
+    recursion::{merge_tree, ChainPolicy, MergePolicy, ProofTuple, TwoToOnePolicy},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+// the same leaves merged with every arity, reporting layers, merges and time; the root
+// states its depth and leaf count
+fn bench<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    P: MergePolicy<F, D>,
+    const D: usize,
+>(
+    name: &str,
+    policy: &P,
+    leaves: &[ProofTuple<F, C, D>],
+    expected: &[F],
+    proof_mode: ProofMode,
+) -> Result<()>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    println!("{} with {} leaves", name, leaves.len());
+    for arity in [2, 4, 8] {
+        let tmp = Instant::now();
+        let root = merge_tree(policy, leaves, arity, proof_mode)?;
+        let elapsed = tmp.elapsed().as_millis();
+        ensure!(root.statement() == expected, "unexpected public inputs");
+        ensure!(
+            root.leaves() == leaves.len() as u64,
+            "unexpected leaf count"
+        );
+        let data = root.verifier_data();
+        let sizes = proof_sizes(&root.proof, &data)?;
+        data.verify(root.proof.clone())?;
+        println!(
+            "  arity {}: {} layers, {} leaves, {} merges, root degree {}, took {}ms, proof size {}",
+            arity,
+            root.depth,
+            root.leaves(),
+            (leaves.len() - 1) / (arity - 1),
+            root.cd.degree(),
+            elapsed,
+            sizes
+        );
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // Two workloads aggregated with arity 2, 4 and 8: a chain of x -> 5x steps whose
+    // root shows (x, 5^n x), and a Merkle subset check whose root shows the roots of the
+    // original tree and of a tree with some leaves zeroed.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    // 2^6 = 4^3 = 8^2, merge_tree needs a power of each arity
+    const NUM_LEAVES: usize = 64;
+    let proof_mode = ProofMode::from_args();
+    println!("Proof mode: {}", proof_mode);
+
+    // pow5 chain: each leaf proves output = 5 * input
+    let circuit = Pow5Circuit::<F, C, D>::new(proof_mode);
+    let x0 = F::from_canonical_u64(7);
+    let mut x = x0;
+    let mut leaves = Vec::new();
+    for _ in 0..NUM_LEAVES {
+        leaves.push(ProofTuple::leaf(circuit.prove(x)?, &circuit.data));
+        x *= F::from_canonical_u64(5);
+    }
+    bench("pow5 chain", &ChainPolicy { width: 1 }, &leaves, &[x0, x], proof_mode)?;
+
+    // Merkle subset: each leaf proves that the subset leaf is the original one or zero
+    let circuit = SubsetCircuit::<F, C, H, D>::new(proof_mode);
+    let hash_of_zero = zero_hash::<F, H>();
+
+    let original_leaves = (0..NUM_LEAVES)
+        .map(|i| vec![F::from_canonical_usize(i), F::ONE, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let subset_leaves = original_leaves
+        .iter()
+        .enumerate()
+        .map(|(i, leaf)| {
+            if i % 3 == 0 {
+                hash_of_zero.elements.to_vec()
+            } else {
+                leaf.clone()
+            }
+        })
+        .collect::<Vec<Vec<F>>>();
+    let mut leaves = Vec::new();
+    for (a, b) in original_leaves.iter().zip(subset_leaves.iter()) {
+        leaves.push(ProofTuple::leaf(circuit.prove(a, b)?, &circuit.data));
+    }
+    let expected = [
+        MerkleTree::<F, H>::new(original_leaves, 0).cap.0[0].elements,
+        MerkleTree::<F, H>::new(subset_leaves, 0).cap.0[0].elements,
+    ]
+    .concat();
+    bench(
+        "Merkle subset",
+        &TwoToOnePolicy::<H>::new(),
+        &leaves,
+        &expected,
+        proof_mode,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{secp256k1_scalar::Secp256K1Scalar, types::Sample},
+    plonk::{
+        circuit_data::VerifierCircuitData,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    },
+};
+use plonky2_examples::{
+    batch_ecdsa::{batch_digest, ground_proof, recursive_tree},
+    compress::proof_sizes,
+    ecdsa::{keygen, sign, EcdsaCircuit},
+    zk::{ProofMode, PublicKeyMode},
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // BATCH_SIZE signers each sign a message, every signature gets its own proof
+    // and the proofs are merged into one whose public input is a digest of all
+    // (message, public key) pairs, like a roll-up would post on chain.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    // proofs merged per recursive circuit, BATCH_SIZE must be a power of it
+    const ARITY: usize = 2;
+    const BATCH_SIZE: usize = 4;
+
+    let tmp = Instant::now();
+    let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, ProofMode::Standard);
+
+    let mut pairs = Vec::new();
+    let mut ground_proofs = Vec::new();
+    for i in 0..BATCH_SIZE {
+        let (sk, pk) = keygen();
+        let msg = Secp256K1Scalar::rand();
+        let sig = sign(msg, sk);
+        ground_proofs.push(ground_proof(&circuit, msg, sig, &pk)?);
+        pairs.push((msg, pk));
+        println!("Signature proof {} done", i);
+    }
+
+    let final_proof = recursive_tree(&ground_proofs, ARITY, proof_mode)?;
+    println!("Computation took {}ms", tmp.elapsed().as_millis());
+    println!("Final proofs public inputs: {:#?}", final_proof.proof.public_inputs);
+
+    ensure!(
+        final_proof.statement() == batch_digest::<F>(&pairs)?.elements,
+        "batch digest does not match the signed pairs"
+    );
+    ensure!(
+        final_proof.leaves() == BATCH_SIZE as u64,
+        "proof covers another number of signatures"
+    );
+
+    println!("Final proof mode: {}", final_proof.mode());
+    let data = VerifierCircuitData::<F, C, D> {
+        verifier_only: final_proof.vd,
+        common: final_proof.cd,
+    };
+    println!("Proof size: {}", proof_sizes(&final_proof.proof, &data)?);
+    data.verify(final_proof.proof)?;
+    println!("Batch of {} signatures verified", BATCH_SIZE);
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, Sample},
+    hash::{hash_types::HashOut, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+    plonk::{
+        circuit_data::VerifierCircuitData,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    },
+};
+use plonky2_examples::{
+    batch_update::{ground_proofs, recursive_tree},
+    compress::proof_sizes,
+    merkle_update::MerkleUpdateCircuit,
+    recursion::is_power_of,
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // A roll-up block with K state updates. Each update gets its own proof and the
+    // proofs are chained into one that goes from the state root before the block
+    // to the state root after it.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 4;
+    // proofs merged per recursive circuit, K must be a power of it
+    const ARITY: usize = 2;
+    const K: usize = 4;
+
+    let leaves = (0..1 << HEIGHT)
+        .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let tree = MerkleTree::<F, H>::new(leaves, 0);
+
+    // the same leaf may be updated more than once
+    let updates = [3, 9, 3, 15].map(|i| (i, HashOut::<F>::rand()));
+    assert_eq!(updates.len(), K);
+    ensure!(is_power_of(K, ARITY), "K must be a power of ARITY");
+
+    let tmp = Instant::now();
+    let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+    let mut final_tree = tree.clone();
+    let proofs = ground_proofs(&circuit, &mut final_tree, &updates)?;
+    let final_proof = recursive_tree(&proofs, ARITY, proof_mode)?;
+    println!("Computation took {}ms", tmp.elapsed().as_millis());
+    println!("Final proofs public inputs: {:#?}", final_proof.proof.public_inputs);
+
+    ensure!(final_proof.proof.public_inputs[0..4] == tree.cap.0[0].elements);
+    ensure!(final_proof.proof.public_inputs[4..8] == final_tree.cap.0[0].elements);
+    ensure!(final_proof.leaves() == K as u64, "proof covers another number of updates");
+
+    println!("Final proof mode: {}", final_proof.mode());
+    let data = VerifierCircuitData::<F, C, D> {
+        verifier_only: final_proof.vd,
+        common: final_proof.cd,
+    };
+    println!("Proof size: {}", proof_sizes(&final_proof.proof, &data)?);
+    data.verify(final_proof.proof)?;
+    println!("{} updates verified", K);
+
+    Ok(())
+}
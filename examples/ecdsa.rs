@@ -0,0 +1,59 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{secp256k1_scalar::Secp256K1Scalar, types::Sample},
+    hash::hash_types::HashOut,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    ecdsa::{keygen, pk_commitment, pk_to_limbs, sign, to_limbs, EcdsaCircuit},
+    zk::{ProofMode, PublicKeyMode},
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // We have a public message hash m and a secp256k1 public key pk.
+    // Proof that "I know a signature (r, s) such that verify(m, (r, s), pk) holds".
+    // The public key is either a public input or hidden behind a Poseidon commitment.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    // sign a random message hash natively
+    let (sk, pk) = keygen();
+    let msg = Secp256K1Scalar::rand();
+    let sig = sign(msg, sk);
+
+    // public key is a public input
+    let tmp = Instant::now();
+    let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    let proof = circuit.prove(msg, sig, &pk, None)?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+
+    let mut expected = to_limbs::<F, _>(msg);
+    expected.extend(pk_to_limbs::<F>(&pk));
+    ensure!(proof.public_inputs == expected, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Signature verified with public key exposed");
+
+    // public key is committed to, blinded with a random salt
+    let tmp = Instant::now();
+    let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Committed, proof_mode);
+    let blinding = HashOut::<F>::rand();
+    let proof = circuit.prove(msg, sig, &pk, Some(blinding))?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+
+    let mut expected = to_limbs::<F, _>(msg);
+    expected.extend(pk_commitment(&pk, blinding).elements);
+    ensure!(proof.public_inputs == expected, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Signature verified with public key committed");
+
+    Ok(())
+}
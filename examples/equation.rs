@@ -2,12 +2,13 @@ use plonky2::{
     field::{goldilocks_field::GoldilocksField, types::Field},
     iop::witness::{PartialWitness, WitnessWrite},
     plonk::{
-        circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+        circuit_builder::CircuitBuilder,
         config::PoseidonGoldilocksConfig,
     },
 };
 
 use anyhow::Result;
+use plonky2_examples::{compress::proof_sizes, zk::ProofMode};
 
 fn main() -> Result<()> {
     // We have a public input c
@@ -16,7 +17,8 @@ fn main() -> Result<()> {
     // The public input is c = 323.
 
     // standard proof setup
-    let config = CircuitConfig::standard_recursion_config();
+    let proof_mode = ProofMode::from_args();
+    let config = proof_mode.config();
     type F = GoldilocksField;
     type C = PoseidonGoldilocksConfig;
     const D: usize = 2;
@@ -45,7 +47,9 @@ fn main() -> Result<()> {
 
     // Generate proof
     let proof = data.prove(pw)?;
+    println!("Proof size: {}", proof_sizes(&proof, &data.verifier_data())?);
     data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&data.common));
 
     Ok(())
 }
\ No newline at end of file
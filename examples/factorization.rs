@@ -0,0 +1,70 @@
+use anyhow::{ensure, Result};
+use num::BigUint;
+use plonky2::{
+    field::types::PrimeField64,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    factor::{biguint_from_limbs, random_prime, FactorCircuit},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // We have a public integer n
+    // Proof that "I know a and b such that a * b = n, with 1 < a, b"
+    // Unlike equation.rs, n can be much larger than the field, e.g. an RSA-2048 modulus,
+    // and the trivial factorization 1 * n is rejected.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    // equation.rs again, now over integers: 17 * 19 = 323
+    let circuit = FactorCircuit::<F, C, D>::new(32, 32, proof_mode);
+    let proof = circuit.prove(&BigUint::from(17u32), &BigUint::from(19u32))?;
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("323 = 17 * 19 verified");
+    ensure!(circuit
+        .prove(&BigUint::from(1u32), &BigUint::from(323u32))
+        .is_err());
+    println!("323 = 1 * 323 rejected");
+
+    // RSA-2048 modulus with private 1024-bit p and q
+    let mut rng = rand::thread_rng();
+    let tmp = Instant::now();
+    let p = random_prime(1024, &mut rng);
+    let q = random_prime(1024, &mut rng);
+    let n = &p * &q;
+    println!(
+        "Generated a {}-bit modulus in {}ms",
+        n.bits(),
+        tmp.elapsed().as_millis()
+    );
+
+    let tmp = Instant::now();
+    let circuit = FactorCircuit::<F, C, D>::new(1024, 1024, proof_mode);
+    let proof = circuit.prove(&p, &q)?;
+    println!(
+        "Circuit degree: {}, proving took {}ms",
+        circuit.data.common.degree(),
+        tmp.elapsed().as_millis()
+    );
+
+    // public inputs are the limbs of n
+    let limbs = proof
+        .public_inputs
+        .iter()
+        .map(|x| x.to_canonical_u64() as u32)
+        .collect::<Vec<u32>>();
+    ensure!(biguint_from_limbs(&limbs) == n);
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Knowledge of the factors of the RSA-2048 modulus verified");
+
+    Ok(())
+}
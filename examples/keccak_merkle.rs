@@ -0,0 +1,105 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitConfig,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    },
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    keccak::{
+        keccak256, keccak256_circuit, limbs_to_digest, verify_keccak_merkle_proof,
+        KeccakInclusionCircuit, KeccakMerkleTree,
+    },
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn main() -> Result<()> {
+    // We have an Ethereum-style Keccak-256 Merkle tree built outside plonky2 and want
+    // to prove "this leaf is in the tree with this root" in a plonky2 proof.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 3;
+
+    // the native hash is the usual Keccak-256
+    ensure!(
+        to_hex(&keccak256(b"")) == "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+
+    // cross-check the in-circuit hash on a message longer than one 136-byte block
+    let message = (0..200).map(|i| i as u8).collect::<Vec<u8>>();
+    let config = CircuitConfig::standard_recursion_config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let bytes = builder.add_virtual_targets(message.len());
+    let digest = keccak256_circuit(&mut builder, &bytes);
+    builder.register_public_inputs(&digest.map(|limb| limb.0));
+    let data = builder.build::<C>();
+    let mut pw = PartialWitness::<F>::new();
+    for (&target, &byte) in bytes.iter().zip(message.iter()) {
+        pw.set_target(target, F::from_canonical_u8(byte));
+    }
+    let proof = data.prove(pw)?;
+    let limbs = proof
+        .public_inputs
+        .iter()
+        .map(|x| x.to_canonical_u64() as u32)
+        .collect::<Vec<u32>>();
+    ensure!(limbs_to_digest(&limbs) == keccak256(&message));
+    println!("Proof size: {}", proof_sizes(&proof, &data.verifier_data())?);
+    data.verify(proof)?;
+    println!("Keccak-256 of {} bytes matches the native hash", message.len());
+
+    // leaves are 32-byte big-endian integers, like abi.encode(uint256)
+    let leaves = (0..1u64 << HEIGHT)
+        .map(|i| {
+            let mut leaf = vec![0u8; 32];
+            leaf[24..].copy_from_slice(&(1000 * i).to_be_bytes());
+            leaf
+        })
+        .collect::<Vec<Vec<u8>>>();
+    let tree = KeccakMerkleTree::new(leaves.clone());
+    println!("Keccak root: 0x{}", to_hex(&tree.root()));
+
+    let leaf_index = 5;
+    verify_keccak_merkle_proof(
+        &leaves[leaf_index],
+        leaf_index,
+        tree.root(),
+        &tree.prove(leaf_index),
+    )?;
+
+    let tmp = Instant::now();
+    let circuit = KeccakInclusionCircuit::<F, C, D>::new(32, HEIGHT, proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    let proof = circuit.prove(&tree, leaf_index)?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+
+    // public inputs are the root limbs followed by the leaf bytes
+    let root_limbs = proof.public_inputs[0..8]
+        .iter()
+        .map(|x| x.to_canonical_u64() as u32)
+        .collect::<Vec<u32>>();
+    ensure!(limbs_to_digest(&root_limbs) == tree.root());
+    ensure!(proof.public_inputs[8..]
+        .iter()
+        .map(|x| x.to_canonical_u64() as u8)
+        .eq(leaves[leaf_index].iter().copied()));
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Inclusion of leaf {} under the Keccak root verified", leaf_index);
+
+    Ok(())
+}
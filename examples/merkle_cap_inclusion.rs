@@ -0,0 +1,74 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Field,
+    hash::{hash_types::HashOut, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    },
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    merkle::{
+        add_virtual_merkle_proof_to_cap, set_merkle_proof_witness,
+        verify_merkle_proof_to_cap_circuit,
+    },
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // We have a public Merkle cap of height 2, i.e. the 4 nodes that sit 2 layers
+    // below the root, and a public leaf.
+    // Proof that "the leaf is in the tree", without folding the cap into a single root.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const HEIGHT: usize = 8;
+    const CAP_HEIGHT: usize = 2;
+
+    let leaves = (0..1 << HEIGHT)
+        .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let tree = MerkleTree::<F, PoseidonHash>::new(leaves.clone(), CAP_HEIGHT);
+
+    // standard proof setup
+    let proof_mode = ProofMode::from_args();
+    let config = proof_mode.config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+
+    // public cap and leaf, private position in the tree
+    let cap = builder.add_virtual_cap(CAP_HEIGHT);
+    let leaf = builder.add_virtual_hash();
+    let path = add_virtual_merkle_proof_to_cap(&mut builder, HEIGHT, CAP_HEIGHT);
+    verify_merkle_proof_to_cap_circuit::<PoseidonHash, F, D>(&mut builder, leaf, &path, &cap);
+    for entry in cap.0.iter() {
+        builder.register_public_inputs(&entry.elements);
+    }
+    builder.register_public_inputs(&leaf.elements);
+
+    println!(
+        "Path hashes: {} ({} up to a single root)",
+        path.siblings.len(),
+        HEIGHT
+    );
+
+    let data = builder.build::<C>();
+
+    let leaf_index = 201;
+    let mut pw = PartialWitness::<F>::new();
+    pw.set_cap_target(&cap, &tree.cap);
+    pw.set_hash_target(leaf, HashOut::from_partial(&leaves[leaf_index]));
+    set_merkle_proof_witness(&mut pw, &path, leaf_index, &tree.prove(leaf_index));
+
+    let proof = data.prove(pw)?;
+    ensure!(proof.public_inputs[0..16] == tree.cap.flatten());
+    println!("Proof size: {}", proof_sizes(&proof, &data.verifier_data())?);
+    data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&data.common));
+    println!("Inclusion of leaf {} under the cap verified", leaf_index);
+
+    Ok(())
+}
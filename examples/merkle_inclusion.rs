@@ -4,6 +4,16 @@ use plonky2::{
     hash::{merkle_tree::MerkleTree, hash_types::RichField},
     hash::{poseidon::PoseidonHash, merkle_tree::MerkleCap, merkle_proofs::MerkleProof}, plonk::config::Hasher
 };
+use plonky2::{
+    hash::hash_types::HashOut,
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{circuit_builder::CircuitBuilder, config::PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    merkle::{add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness},
+    zk::ProofMode,
+};
 use anyhow::{Result, Ok, ensure};
 
 // usage of merkle tree library very basicly
@@ -78,5 +88,28 @@ fn main() -> Result<()> {
     let proof = merkle_tree.prove(leaf_index);
     println!("ROOT {:#?} ", root(&merkle_tree.cap));
     deneme::<F, H>(leaves[leaf_index].clone(), leaf_index, &merkle_tree.cap, &proof)?;
+
+    // The same check in a circuit: the root is public, the leaf and its path are
+    // private, so the proof only says "some leaf is in the tree with this root".
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    let proof_mode = ProofMode::from_args();
+    let mut builder = CircuitBuilder::<F, D>::new(proof_mode.config());
+    let leaf = builder.add_virtual_hash();
+    let path = add_virtual_merkle_proof(&mut builder, 2);
+    let computed = merkle_root_circuit::<H, F, D>(&mut builder, leaf, &path);
+    builder.register_public_inputs(&computed.elements);
+    let data = builder.build::<C>();
+
+    // a cap of height 0 is the root, so its path goes all the way up
+    let full_tree = MerkleTree::<F, H>::new(leaves.clone(), 0);
+    let mut pw = PartialWitness::<F>::new();
+    pw.set_hash_target(leaf, HashOut::from_partial(&leaves[leaf_index]));
+    set_merkle_proof_witness(&mut pw, &path, leaf_index, &full_tree.prove(leaf_index));
+    let circuit_proof = data.prove(pw)?;
+    ensure!(circuit_proof.public_inputs == root(&merkle_tree.cap).elements, "Invalid Merkle root.");
+    println!("Proof size: {}", proof_sizes(&circuit_proof, &data.verifier_data())?);
+    data.verify(circuit_proof)?;
+    println!("Verified a {} proof", ProofMode::of(&data.common));
     Ok(())
 }
@@ -0,0 +1,80 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Field,
+    hash::{hash_types::HashOut, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitConfig,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    },
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    merkle::{add_virtual_merkle_proof, merkle_root_circuit},
+    multiproof::{
+        fixed_index_multiproof_circuit, leaf_digest, num_hashes, prove_multi, verify_multi,
+        FixedIndexMultiproofCircuit,
+    },
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // Proof that k leaves are in the tree with a given root, using one multiproof
+    // instead of k separate Merkle paths.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 10;
+    let indices = [2, 3, 17, 100, 101, 102, 600, 1023];
+    let k = indices.len();
+
+    // leaves wider than a digest, hashed as MerkleTree does
+    let leaves = (0..1 << HEIGHT)
+        .map(|i| vec![F::from_canonical_u64(i), F::ONE, F::ZERO, F::ZERO, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let tree = MerkleTree::<F, H>::new(leaves.clone(), 0);
+    let root = tree.cap.0[0];
+
+    // native proof and verification
+    let proof = prove_multi(&tree, &indices)?;
+    let proven_leaves = indices
+        .iter()
+        .map(|&i| leaf_digest::<F, H>(&leaves[i]))
+        .collect::<Vec<HashOut<F>>>();
+    verify_multi::<F, H>(HEIGHT, &indices, &proven_leaves, root, &proof)?;
+    println!("Siblings: {} ({} for separate paths)", proof.siblings.len(), k * HEIGHT);
+    println!("Hashes: {} ({} for separate paths)", num_hashes(HEIGHT, &indices)?, k * HEIGHT);
+
+    // in the circuit, built for these indices
+    let circuit = FixedIndexMultiproofCircuit::<F, C, H, D>::new(HEIGHT, &indices, proof_mode)?;
+    let zk_proof = circuit.prove(&tree, &proof)?;
+    ensure!(zk_proof.public_inputs[0..4] == root.elements);
+    // the proven indices close the public inputs
+    let index_inputs = indices.map(F::from_canonical_usize);
+    ensure!(zk_proof.public_inputs[4 + 4 * k..] == index_inputs);
+    println!("Proof size: {}", proof_sizes(&zk_proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(zk_proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Multiproof of {} leaves verified in the circuit", k);
+
+    // gate counts of the multiproof and of k separate paths
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    fixed_index_multiproof_circuit::<H, F, D>(&mut builder, HEIGHT, &indices)?;
+    let multiproof_gates = builder.num_gates();
+
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let root_target = builder.add_virtual_hash();
+    for _ in 0..k {
+        let leaf = builder.add_virtual_hash();
+        let path = add_virtual_merkle_proof(&mut builder, HEIGHT);
+        let computed_root = merkle_root_circuit::<H, F, D>(&mut builder, leaf, &path);
+        builder.connect_hashes(computed_root, root_target);
+    }
+    println!("Gates: {} ({} for separate paths)", multiproof_gates, builder.num_gates());
+
+    Ok(())
+}
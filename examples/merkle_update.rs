@@ -0,0 +1,52 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, Sample},
+    hash::{hash_types::HashOut, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    merkle_update::MerkleUpdateCircuit,
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // A roll-up keeps its state in a Merkle tree and publishes only the root.
+    // Changing one account leaf is proven as a transition from the old root to the new root.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 4;
+
+    let leaves = (0..1 << HEIGHT)
+        .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let tree = MerkleTree::<F, H>::new(leaves.clone(), 0);
+
+    let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, proof_mode);
+
+    let leaf_index = 6;
+    let new_leaf = HashOut::<F>::rand();
+    let (proof, new_tree) = circuit.prove(&tree, leaf_index, new_leaf)?;
+
+    // the root proven in the circuit is the one of a freshly built tree
+    let mut new_leaves = leaves;
+    new_leaves[leaf_index] = new_leaf.elements.to_vec();
+    let expected_root = MerkleTree::<F, H>::new(new_leaves, 0).cap.0[0];
+    ensure!(new_tree.cap.0[0] == expected_root);
+    ensure!(proof.public_inputs[0..4] == tree.cap.0[0].elements);
+    ensure!(proof.public_inputs[4..8] == expected_root.elements);
+
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Update of leaf {} verified", leaf_index);
+    println!("Old root: {:?}", tree.cap.0[0]);
+    println!("New root: {:?}", expected_root);
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use pow_of5_circuit::{F, Circuit};
 use plonky2::field::types::Field;
+use plonky2_examples::zk::ProofMode;
 
 mod pow_of5_circuit;
 
@@ -12,7 +13,7 @@ fn main() {
     };
 
     // make circuit 
-    let (input, output, circuit_instance) = circuit.make_circuit().unwrap();
+    let (input, output, circuit_instance) = circuit.make_circuit(ProofMode::from_args()).unwrap();
 
     // prove and verify data
     circuit.create_and_verify_proof( input, output, circuit_instance).unwrap()
@@ -6,10 +6,11 @@ use plonky2::{
     },
     plonk::{
         circuit_builder::CircuitBuilder,
-        circuit_data::{CircuitConfig, CircuitData},
+        circuit_data::CircuitData,
         config::PoseidonGoldilocksConfig,
     },
 };
+use plonky2_examples::{compress::proof_sizes, zk::ProofMode};
 
 
 pub type F = GoldilocksField;
@@ -24,9 +25,9 @@ pub struct Circuit {
 
 
 impl Circuit {
-    pub fn make_circuit(&self) -> Result<(Target, Target, CircuitData<F, C, 2>)> {
-        // use standard config 
-        let config = CircuitConfig::standard_recursion_config();
+    pub fn make_circuit(&self, proof_mode: ProofMode) -> Result<(Target, Target, CircuitData<F, C, 2>)> {
+        // use standard config, blinded in zero-knowledge mode
+        let config = proof_mode.config();
         // create builder from config
         let mut builder = CircuitBuilder::new(config);
 
@@ -58,9 +59,22 @@ impl Circuit {
         
         // plonky2 auto compute full witness base on circuit
         let proof = circuit.prove(pw).unwrap();
+        println!("Proof size: {}", proof_sizes(&proof, &circuit.verifier_data())?);
 
         // verify proof 
-        circuit.verify(proof)
+        circuit.verify(proof)?;
+        println!("Verified a {} proof", ProofMode::of(&circuit.common));
+        Ok(())
     }
 }
 
+// also runs on its own: cargo run --example pow_of5_circuit --release -- --zk
+#[allow(dead_code)]
+fn main() -> Result<()> {
+    let circuit = Circuit {
+        input: F::from_canonical_u64(25),
+        output: F::from_canonical_u64(125),
+    };
+    let (input, output, circuit_instance) = circuit.make_circuit(ProofMode::from_args())?;
+    circuit.create_and_verify_proof(input, output, circuit_instance)
+}
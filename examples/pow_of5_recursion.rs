@@ -1,128 +1,27 @@
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use rand::rngs::OsRng;
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
 use std::time::Instant;
 use plonky2::field::types::Field;
-use plonky2::iop::witness::{PartialWitness, WitnessWrite};
-use plonky2::plonk::circuit_builder::CircuitBuilder;
-use plonky2::plonk::circuit_data::{CircuitConfig, CommonCircuitData, VerifierOnlyCircuitData, VerifierCircuitData};
+use plonky2::plonk::circuit_data::VerifierCircuitData;
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig};
-use plonky2::plonk::proof::ProofWithPublicInputs;
 use plonky2::hash::hash_types::RichField;
 
 use plonky2::field::extension::Extendable;
+use plonky2_examples::compress::proof_sizes;
+use plonky2_examples::pow5::Pow5Circuit;
+use plonky2_examples::recursion::{merge_tree, ChainPolicy, ProofTuple};
+use plonky2_examples::zk::ProofMode;
 
-// Recursively validated proof of 5**x 
-#[derive(Clone)]
-pub struct ProofTuple<F: RichField+Extendable<D>, C:GenericConfig<D, F=F>, const D: usize>{
-    proof: ProofWithPublicInputs<F, C, D>,
-    vd: VerifierOnlyCircuitData<C, D>,
-    cd: CommonCircuitData<F, D>,
-    depth: u32,
-}
-// generates ground proof for a step
-pub fn ground_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F=F>, const D: usize, const B: usize>(inp:u64)->ProofTuple<F,C,D>{
-    
-    let config = CircuitConfig::standard_recursion_config();
-    let mut builder = CircuitBuilder::<F, D>::new(config);
-
-    // aritmatic circuit to output = input * 5        
-    let input = builder.add_virtual_target();
-    let output = builder.mul_const(F::from_canonical_u64(5), input);
-    let mut pw = PartialWitness::new();
-    pw.set_target(input, F::from_canonical_u64(inp));
-    builder.register_public_input(input);
-    builder.register_public_input(output);
-
-
-    let data = builder.build::<C>();
-    let proof = data.prove(pw).unwrap();
-
-    ProofTuple{
-        proof, 
-        vd: data.verifier_only, 
-        cd: data.common,
-        depth: 0
-    }
-}
-
-
-/// This function merges two proofs with 2 public inputs each, treated as follows: 0 is "input", 1 is "output"
-/// It requires that output of the first proof is the input of the second
-/// It exposes the input of the first proof and the output of the second
-pub fn recursive_proof<
-F: RichField + Extendable<D>,
-C: GenericConfig<D, F = F>,
-const D: usize,
->(
-inner_l: &ProofTuple<F, C, D>, inner_r: &ProofTuple<F,C,D>,
-) -> Result<ProofTuple<F, C, D>>
-where
-C::Hasher: AlgebraicHasher<F>,   
-{
-    let config = CircuitConfig::standard_recursion_config();
-    let mut builder = CircuitBuilder::<F, D>::new(config);
-//    assert_eq!(inner_l.depth, inner_r.depth, "Trying to merge proofs of different depth!");
-
-    let pt_l = builder.add_virtual_proof_with_pis(& inner_l.cd);
-    let pt_r = builder.add_virtual_proof_with_pis(& inner_r.cd);
-
-    let inner_vdt_l = builder.add_virtual_verifier_data(inner_l.cd.config.fri_config.cap_height);
-    let inner_vdt_r = builder.add_virtual_verifier_data(inner_r.cd.config.fri_config.cap_height);
-    
-    builder.verify_proof::<C>(&pt_l, &inner_vdt_l, &inner_l.cd);
-    builder.verify_proof::<C>(&pt_r, &inner_vdt_r, &inner_r.cd);
-
-    // the output of pt_l is constrained to the input of pt_r
-    builder.connect(pt_l.public_inputs[1], pt_r.public_inputs[0]);
-
-    let mut pw = PartialWitness::new();
-    pw.set_proof_with_pis_target::<C, D>(& pt_l, & inner_l.proof);
-    pw.set_proof_with_pis_target::<C, D>(& pt_r, & inner_r.proof);
-    pw.set_verifier_data_target::<C,D>(& inner_vdt_l, & inner_l.vd);
-    pw.set_verifier_data_target::<C,D>(& inner_vdt_r, & inner_r.vd);
-
-    builder.register_public_input(pt_l.public_inputs[0]);
-    builder.register_public_input(pt_r.public_inputs[1]);
-
-    let data = builder.build::<C>();
-    
-    let proof = data.prove(pw).unwrap();
-    Ok(ProofTuple{proof, vd: data.verifier_only, cd: data.common, depth: inner_l.depth+1})
-
-}
-
-// this function generates the tree of proofs recursively
-pub fn recursive_tree<F: RichField + Extendable<D>, C:GenericConfig<D, F=F>, const D: usize>
-    (
-        location: u64,
-        mut ground_size: usize,
-        height: usize,
-        trivial_proofs: &Vec<ProofTuple<F,C,D>>,
-    )->ProofTuple<F,C,D> where <C as GenericConfig<D>>::Hasher: AlgebraicHasher<F>
-    {
-        if height > 1 {
-            ground_size = trivial_proofs.len() as usize;
-            ground_size = ground_size / 2;
-            let sec = ground_size as u64;
-            let first_tuple = recursive_tree::<F, C, D>(location, ground_size, height-1, &trivial_proofs[0..ground_size].to_vec());
-            let second_tuple = recursive_tree::<F, C, D>(location+sec, ground_size,  height-1, &trivial_proofs[ground_size..ground_size*2].to_vec());
-            let merged_tuple = recursive_proof(&first_tuple, &second_tuple).unwrap();
-            return merged_tuple;
-        }
-        else {
-            let merged_tuple = recursive_proof(&trivial_proofs[0], &trivial_proofs[1]).unwrap();
-            return merged_tuple;
-        }
-    }
+// Recursively validated proof of 5**x
 
 // This function runs the whole thing.
 pub fn run<
         F: RichField + Extendable<D>,
         C:GenericConfig<D, F=F>,
-        const D: usize> (init_value: u64)
+        const D: usize> (init_value: u64, proof_mode: ProofMode)
             ->
         Result<VerifierCircuitData<F,C,D>>
             where
@@ -134,26 +33,29 @@ pub fn run<
 
     const BATCH_SIZE: usize = 16;
 
-    // this is the total depth of recursion batches. pow(2, depth)*BATCH_SIZE hashes must be infeasible to do sequentially.
-    const DEPTH: usize = 4;
+    // number of proofs merged at each level of the tree, BATCH_SIZE must be a power of it.
+    const ARITY: usize = 4;
 
     let tmp = Instant::now();
     // Trivial proof phase computation, it can be separated into the precompute phase with the small modification of the circuit; and recursive circuit
     // I'm a bit too lazy to mess with this now. This phase will take < time than the main phase in any case.
+    let circuit = Pow5Circuit::<F,C,D>::new(proof_mode);
     let mut trivial_proofs = Vec::new();
-    trivial_proofs.push(ground_proof::<F,C,D,BATCH_SIZE>(init_value));
-    let mut x = init_value;
-    for i in 1..BATCH_SIZE {
-        println!("={:#?}", trivial_proofs[i-1].proof.public_inputs);
-        x = x*5;
-        trivial_proofs.push(ground_proof::<F,C,D,BATCH_SIZE>(x));
-
+    let mut x = F::from_canonical_u64(init_value);
+    for _ in 0..BATCH_SIZE {
+        let proof = circuit.prove(x)?;
+        println!("={:#?}", proof.public_inputs);
+        trivial_proofs.push(ProofTuple::leaf(proof, &circuit.data));
+        x *= F::from_canonical_u64(5);
     }
     println!("FOR LOOOOP FİNİSHED");
 
     println!("Lets come to final proof!");
-    let final_proof = recursive_tree::<F,C,D>(0, BATCH_SIZE, DEPTH, &trivial_proofs);
+    let final_proof = merge_tree(&ChainPolicy { width: 1 }, &trivial_proofs, ARITY, proof_mode)?;
     println!("Final proofs public inputs: {:#?}", final_proof.proof.public_inputs);
+    // the last two public inputs are the depth of the tree and its number of leaves
+    println!("The proof covers {} multiplications by 5, {} layers deep", final_proof.leaves(), final_proof.depth);
+    ensure!(final_proof.leaves() == BATCH_SIZE as u64);
     
     let tmp2 = Instant::now();
     println!("Computation took {}ms", (tmp2-tmp).as_millis());
@@ -161,8 +63,9 @@ pub fn run<
     println!("Allegedly, the result of our poseidon is: {:#?}",
         final_proof.proof.public_inputs,
      );
-    println!("Proof size: {} bytes\n",final_proof.proof.to_bytes().len());
-    Ok(VerifierCircuitData::<F,C,D>{verifier_only: final_proof.vd, common: final_proof.cd})
+    let data = VerifierCircuitData::<F,C,D>{verifier_only: final_proof.vd, common: final_proof.cd};
+    println!("Proof size: {}\n", proof_sizes(&final_proof.proof, &data)?);
+    Ok(data)
 }
 
 pub fn test() -> Result<()> {
@@ -181,7 +84,10 @@ pub fn test() -> Result<()> {
     let mut cutoff = (rng.next_u64() % 35) as u128;
 
     
-    let vd1 = run::<F,C,D>(init_value)?; // Currently not using rayon. Maybe should (it gives some performance gain even on my machine).
+    let proof_mode = ProofMode::from_args();
+    println!("Proof mode: {}", proof_mode);
+
+    let vd1 = run::<F,C,D>(init_value, proof_mode)?; // Currently not using rayon. Maybe should (it gives some performance gain even on my machine).
 
     println!("Run again to check that the verifier data of the final proof is the same!\n");
 
@@ -189,7 +95,7 @@ pub fn test() -> Result<()> {
 
 
 
-    let vd2 = run::<F,C,D>(init_value)?;
+    let vd2 = run::<F,C,D>(init_value, proof_mode)?;
 
     println!("Checking that verifier circuit data is the same for two proofs! \n");
 
@@ -0,0 +1,81 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, Sample},
+    hash::hash_types::HashOut,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    rsa::{digest_to_words, keygen, modulus_commitment, pk_to_limbs, sign, verify, RsaCircuit},
+    sha256::sha256,
+    zk::{ProofMode, PublicKeyMode},
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // We have a public SHA-256 digest and an RSA-2048 public key (n, 65537).
+    // Proof that "I know a PKCS#1 v1.5 signature s of this digest under n",
+    // i.e. s^65537 mod n is the padded digest.
+    // The modulus is either a public input or hidden behind a Poseidon commitment.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    let mut rng = rand::thread_rng();
+    let tmp = Instant::now();
+    let sk = keygen(&mut rng);
+    let pk = sk.to_public();
+    println!(
+        "Generated a {}-bit key in {}ms",
+        pk.n.bits(),
+        tmp.elapsed().as_millis()
+    );
+
+    // sign natively, as any RSA library would
+    let msg = b"plonky2 verifies RSA signatures";
+    let signature = sign(&sk, msg);
+    ensure!(verify(&pk, msg, &signature));
+    let digest = sha256(msg);
+    let words = digest_to_words(&digest)
+        .into_iter()
+        .map(F::from_canonical_u32)
+        .collect::<Vec<F>>();
+
+    // modulus is a public input
+    let tmp = Instant::now();
+    let circuit = RsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    let proof = circuit.prove(&digest, &signature, &pk, None)?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+
+    let mut expected = words.clone();
+    expected.extend(pk_to_limbs::<F>(&pk));
+    ensure!(proof.public_inputs == expected, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Signature verified with modulus exposed");
+
+    // a signature of another message is rejected before proving
+    let other = sign(&sk, b"another message");
+    ensure!(circuit.prove(&digest, &other, &pk, None).is_err());
+    println!("Signature of another message rejected");
+
+    // modulus is committed to, blinded with a random salt
+    let tmp = Instant::now();
+    let circuit = RsaCircuit::<F, C, D>::new(PublicKeyMode::Committed, proof_mode);
+    let blinding = HashOut::<F>::rand();
+    let proof = circuit.prove(&digest, &signature, &pk, Some(blinding))?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+
+    let mut expected = words;
+    expected.extend(modulus_commitment(&pk, blinding).elements);
+    ensure!(proof.public_inputs == expected, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Signature verified with modulus committed");
+
+    Ok(())
+}
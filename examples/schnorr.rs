@@ -0,0 +1,89 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{secp256k1_scalar::Secp256K1Scalar, types::Sample},
+    hash::hash_types::HashOut,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    ecdsa::{self, EcdsaCircuit},
+    ecgfp5::{order, point_to_limbs},
+    schnorr::{keygen, sign, SchnorrCircuit, SchnorrSignature},
+    zk::{ProofMode, PublicKeyMode},
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // We have a public message digest m and an EcGFp5 public key pk.
+    // Proof that "I know a Schnorr signature (R, s) of m under pk".
+    // The curve is defined over GF(p^5) for the Goldilocks p, so the verifier needs no
+    // non-native arithmetic; we compare its cost against secp256k1 ECDSA in ecdsa.rs.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    let mut rng = rand::thread_rng();
+    let (sk, pk) = keygen(&mut rng);
+    let msg = HashOut::<F>::rand();
+    let sig = sign(msg, &sk, &mut rng);
+
+    let tmp = Instant::now();
+    let circuit = SchnorrCircuit::<F, C, D>::new(proof_mode);
+    let schnorr_build = tmp.elapsed();
+    let tmp = Instant::now();
+    let proof = circuit.prove(msg, &sig, &pk)?;
+    let schnorr_prove = tmp.elapsed();
+
+    let mut expected = msg.elements.to_vec();
+    expected.extend(point_to_limbs::<F>(&pk.0));
+    ensure!(proof.public_inputs == expected, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Schnorr signature verified");
+
+    // a signature of another message is rejected before proving
+    let other = sign(HashOut::<F>::rand(), &sk, &mut rng);
+    ensure!(circuit.prove(msg, &other, &pk).is_err());
+    println!("Signature of another message rejected");
+    // and so is s + n, which the circuit also refuses since it asserts s < n
+    let malleated = SchnorrSignature {
+        r: sig.r,
+        s: &sig.s + order(),
+    };
+    ensure!(circuit.prove(msg, &malleated, &pk).is_err());
+    println!("Malleated signature (R, s + n) rejected");
+    let schnorr_degree = circuit.data.common.degree();
+
+    // the same statement for secp256k1 ECDSA
+    let (ecdsa_sk, ecdsa_pk) = ecdsa::keygen();
+    let ecdsa_msg = Secp256K1Scalar::rand();
+    let ecdsa_sig = ecdsa::sign(ecdsa_msg, ecdsa_sk);
+    let tmp = Instant::now();
+    let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, proof_mode);
+    let ecdsa_build = tmp.elapsed();
+    let tmp = Instant::now();
+    let proof = circuit.prove(ecdsa_msg, ecdsa_sig, &ecdsa_pk, None)?;
+    let ecdsa_prove = tmp.elapsed();
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("ECDSA signature verified");
+
+    println!("           degree  build(ms)  prove(ms)");
+    println!(
+        "Schnorr  {:>8}  {:>9}  {:>9}",
+        schnorr_degree,
+        schnorr_build.as_millis(),
+        schnorr_prove.as_millis()
+    );
+    println!(
+        "ECDSA    {:>8}  {:>9}  {:>9}",
+        circuit.data.common.degree(),
+        ecdsa_build.as_millis(),
+        ecdsa_prove.as_millis()
+    );
+
+    Ok(())
+}
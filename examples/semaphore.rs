@@ -0,0 +1,66 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Field,
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    semaphore::{identity_tree, nullifier, SemaphoreCircuit},
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // A group of 8 members, every member holds a secret and the group publishes
+    // the Merkle root of H(secret) for all members.
+    // A member proves "I am in the group" without saying which one, and reveals a
+    // nullifier so that signalling twice for the same topic can be detected.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 3;
+
+    let secrets = (0..1 << HEIGHT)
+        .map(|i| HashOut::<F>::from_partial(&[F::from_canonical_u64(1000 + i)]))
+        .collect::<Vec<HashOut<F>>>();
+    let tree = identity_tree::<F, H>(&secrets);
+    let root = tree.cap.0[0];
+
+    let circuit = SemaphoreCircuit::<F, C, H, D>::new(HEIGHT, proof_mode);
+
+    // member 5 signals on topic 1
+    let member = 5;
+    let topic1 = HashOut::<F>::from_partial(&[F::ONE]);
+    let proof1 = circuit.prove(&tree, secrets[member], member, topic1)?;
+    println!("Proof size: {}", proof_sizes(&proof1, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof1.clone())?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+
+    let public_inputs = [
+        root.elements,
+        topic1.elements,
+        nullifier::<F, H>(secrets[member], topic1).elements,
+    ]
+    .concat();
+    ensure!(proof1.public_inputs == public_inputs, "unexpected public inputs");
+    println!("Membership proof verified, nullifier: {:?}", &proof1.public_inputs[8..12]);
+
+    // signalling again on topic 1 reveals the same nullifier
+    let proof2 = circuit.prove(&tree, secrets[member], member, topic1)?;
+    circuit.data.verify(proof2.clone())?;
+    ensure!(proof1.public_inputs[8..12] == proof2.public_inputs[8..12]);
+    println!("Double signal detected");
+
+    // on topic 2 the nullifier is a fresh one
+    let topic2 = HashOut::<F>::from_partial(&[F::TWO]);
+    let proof3 = circuit.prove(&tree, secrets[member], member, topic2)?;
+    circuit.data.verify(proof3.clone())?;
+    ensure!(proof1.public_inputs[8..12] != proof3.public_inputs[8..12]);
+    println!("Signal on another topic accepted");
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::PrimeField64,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    sha256::{digest_to_bytes, num_blocks, Sha256Circuit},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn main() -> Result<()> {
+    // Proof of "I know x such that SHA256(x) = h" with h public, for any x of at most
+    // MAX_LEN bytes. One circuit is checked against the FIPS 180-4 test vectors,
+    // which have different lengths and need one or two blocks.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    const MAX_LEN: usize = 119;
+
+    let vectors: [(&[u8], &str); 4] = [
+        (
+            b"",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            b"abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+        (
+            b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+        ),
+    ];
+
+    let circuit = Sha256Circuit::<F, C, D>::new(MAX_LEN, proof_mode);
+    println!(
+        "Blocks: {}, circuit degree: {}",
+        num_blocks(MAX_LEN),
+        circuit.data.common.degree()
+    );
+
+    for (message, expected) in vectors {
+        let tmp = Instant::now();
+        let proof = circuit.prove(message)?;
+        let words = proof
+            .public_inputs
+            .iter()
+            .map(|x| x.to_canonical_u64() as u32)
+            .collect::<Vec<u32>>();
+        let digest = to_hex(&digest_to_bytes(&words));
+        ensure!(digest == expected, "wrong digest for a {} byte message", message.len());
+        println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+        circuit.data.verify(proof)?;
+        println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+        println!(
+            "SHA256 of {} bytes = {} verified in {}ms",
+            message.len(),
+            digest,
+            tmp.elapsed().as_millis()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{
+        extension::Extendable,
+        types::{Field, Sample},
+    },
+    hash::{
+        hash_types::{HashOut, RichField},
+        merkle_tree::MerkleTree,
+        poseidon::PoseidonHash,
+    },
+    plonk::config::{GenericConfig, KeccakGoldilocksConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    batch_update::{ground_proofs, recursive_tree},
+    compress::proof_sizes,
+    merkle_update::MerkleUpdateCircuit,
+    recursion::ProofTuple,
+    shrink::shrink_proof,
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+// proof sizes and verification time of a recursive proof
+fn report<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    name: &str,
+    proof: &ProofTuple<F, C, D>,
+) -> Result<()> {
+    let data = proof.verifier_data();
+    let sizes = proof_sizes(&proof.proof, &data)?;
+    let tmp = Instant::now();
+    data.verify(proof.proof.clone())?;
+    println!(
+        "{}: degree {}, {} FRI queries, {}, verified in {}ms",
+        name,
+        proof.cd.degree(),
+        proof.cd.config.fri_config.num_query_rounds,
+        sizes,
+        tmp.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // The proof of a batch of Merkle updates, as in batch_merkle_update, wrapped once
+    // more in a high-rate config to make it small before it is published.
+    // The wrapper blinds in zero-knowledge mode, so the tree below it stays standard.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 4;
+    // proofs merged per recursive circuit, K must be a power of it
+    const ARITY: usize = 2;
+    const K: usize = 4;
+
+    let leaves = (0..1 << HEIGHT)
+        .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+        .collect::<Vec<Vec<F>>>();
+    let mut tree = MerkleTree::<F, H>::new(leaves, 0);
+    let updates = [3, 9, 3, 15].map(|i| (i, HashOut::<F>::rand()));
+    assert_eq!(updates.len(), K);
+
+    let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+    let proofs = ground_proofs(&circuit, &mut tree, &updates)?;
+    let root = recursive_tree(&proofs, ARITY, ProofMode::Standard)?;
+    report("Root proof", &root)?;
+
+    let tmp = Instant::now();
+    let shrunk = shrink_proof::<F, C, C, D>(&root, proof_mode)?;
+    println!("Poseidon wrapper took {}ms", tmp.elapsed().as_millis());
+    ensure!(shrunk.proof.public_inputs == root.proof.public_inputs);
+    report("Poseidon wrapper", &shrunk)?;
+
+    let tmp = Instant::now();
+    let shrunk = shrink_proof::<F, C, KeccakGoldilocksConfig, D>(&root, proof_mode)?;
+    println!("Keccak wrapper took {}ms", tmp.elapsed().as_millis());
+    ensure!(shrunk.proof.public_inputs == root.proof.public_inputs);
+    report("Keccak wrapper", &shrunk)?;
+    println!("Wrapper proof mode: {}", shrunk.mode());
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, Sample},
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    smt::{SmtCircuit, SmtStatement, SparseMerkleTree},
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // A key-value store committed to by the root of a sparse Merkle tree.
+    // We prove that a key maps to a value, and that another key is not in the store.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    // field keys, the tree has 64 layers
+    let mut tree = SparseMerkleTree::<F, H>::new(1);
+    let empty_root = tree.root();
+    let keys = [3u64, 17, 1 << 40, u64::MAX - (1 << 32)]
+        .map(|k| vec![F::from_canonical_u64(k)]);
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(key, HashOut::from_partial(&[F::from_canonical_usize(i)]))?;
+    }
+    tree.update(&keys[1], HashOut::rand())?;
+
+    let membership = SmtCircuit::<F, C, H, D>::new(1, SmtStatement::Membership, proof_mode);
+    let proof = membership.prove(&tree, &keys[1])?;
+    ensure!(proof.public_inputs[0..4] == tree.root().elements);
+    ensure!(proof.public_inputs[5..9] == tree.get(&keys[1]).unwrap().elements);
+    println!("Proof size: {}", proof_sizes(&proof, &membership.data.verifier_data())?);
+    membership.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&membership.data.common));
+    println!("Membership of key {} verified", keys[1][0]);
+
+    let non_membership = SmtCircuit::<F, C, H, D>::new(1, SmtStatement::NonMembership, proof_mode);
+    let absent = vec![F::from_canonical_u64(4)];
+    let proof = non_membership.prove(&tree, &absent)?;
+    println!("Proof size: {}", proof_sizes(&proof, &non_membership.data.verifier_data())?);
+    non_membership.data.verify(proof)?;
+    println!("Non-membership of key {} verified", absent[0]);
+
+    // after deleting a key it can be proven absent
+    tree.delete(&keys[2])?;
+    let proof = non_membership.prove(&tree, &keys[2])?;
+    non_membership.data.verify(proof)?;
+    println!("Non-membership of deleted key {} verified", keys[2][0]);
+
+    // deleting everything gives back the empty root
+    for key in [&keys[0], &keys[1], &keys[3]] {
+        tree.delete(key)?;
+    }
+    ensure!(tree.root() == empty_root, "empty tree has a different root");
+
+    // 256-bit keys, the tree has 256 layers
+    let mut tree = SparseMerkleTree::<F, H>::new(4);
+    let key = HashOut::<F>::rand().elements.to_vec();
+    tree.insert(&key, HashOut::rand())?;
+    let membership = SmtCircuit::<F, C, H, D>::new(4, SmtStatement::Membership, proof_mode);
+    let proof = membership.prove(&tree, &key)?;
+    membership.data.verify(proof)?;
+    println!("Membership of a 256-bit key verified");
+
+    // a field key does not fit this tree
+    ensure!(membership.prove(&tree, &[F::ONE]).is_err());
+
+    Ok(())
+}
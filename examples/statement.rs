@@ -0,0 +1,62 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Field,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    statement::{Statement, StatementCircuit},
+    zk::ProofMode,
+};
+
+fn main() -> Result<()> {
+    // Arithmetic statements written as text, the generic version of equation.rs.
+    // The statement and the witness are plain JSON, like files written by someone
+    // who does not touch plonky2.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    // equation.rs: "I know a and b such that a * b = c", with c public
+    let statement = Statement::from_json(r#"{ "statement": "a * b = c", "public": ["c"] }"#)?;
+    let circuit = StatementCircuit::<F, C, D>::new(statement, proof_mode);
+    let proof = circuit.prove(r#"{ "a": 17, "b": 19, "c": 323 }"#)?;
+    ensure!(proof.public_inputs == vec![F::from_canonical_u64(323)]);
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("a * b = 323 verified");
+
+    // several equations, powers, negative values and decimal strings
+    let statement = Statement::from_json(
+        r#"{
+            "statement": "a*b + c^3 - d = out; (a - 1)^2 = e",
+            "public": ["out", "e"]
+        }"#,
+    )?;
+    println!("Public: {:?}, all variables: {:?}", statement.public, statement.variables);
+    let circuit = StatementCircuit::<F, C, D>::new(statement, proof_mode);
+    let witness = r#"{ "a": 17, "b": 19, "c": "3", "d": -1, "out": 351, "e": 256 }"#;
+    let proof = circuit.prove(witness)?;
+    println!("Public inputs: {:?}", proof.public_inputs);
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("a*b + c^3 - d = 351 and (a - 1)^2 = 256 verified");
+
+    // a wrong witness is caught before proving
+    let wrong = r#"{ "a": 17, "b": 19, "c": 3, "d": 1, "out": 351, "e": 256 }"#;
+    match circuit.prove(wrong) {
+        Err(e) => println!("Wrong witness rejected: {}", e),
+        Ok(_) => anyhow::bail!("wrong witness was accepted"),
+    }
+
+    // and so is a malformed statement
+    match Statement::parse("a * (b + = c", &[]) {
+        Err(e) => println!("Malformed statement rejected: {}", e),
+        Ok(_) => anyhow::bail!("malformed statement was accepted"),
+    }
+
+    Ok(())
+}
@@ -4,12 +4,13 @@ use plonky2::{
     iop::target:: Target,
     hash::hash_types::RichField,
     plonk::{
-        circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+        circuit_builder::CircuitBuilder,
         config::PoseidonGoldilocksConfig,
     },
 
 };
 use anyhow::Result;
+use plonky2_examples::{compress::proof_sizes, zk::ProofMode};
 
 fn change_type(v: usize) -> u32 {
     v as u32
@@ -38,7 +39,8 @@ fn main() -> Result<()> {
 
 
     // standard proof setup
-    let config = CircuitConfig::standard_recursion_config();
+    let proof_mode = ProofMode::from_args();
+    let config = proof_mode.config();
     type F = GoldilocksField;
     type C = PoseidonGoldilocksConfig;
     const D: usize = 2;
@@ -115,7 +117,9 @@ fn main() -> Result<()> {
 
     // Generate proof
     let proof = data.prove(pw)?;
+    println!("Proof size: {}", proof_sizes(&proof, &data.verifier_data())?);
     data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&data.common));
 
     Ok(())
 }
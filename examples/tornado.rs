@@ -0,0 +1,64 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::{Field, Sample},
+    hash::{hash_types::HashOut, poseidon::PoseidonHash},
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    tornado::{DepositTree, Note, WithdrawCircuit},
+    zk::ProofMode,
+};
+use std::collections::HashSet;
+
+fn main() -> Result<()> {
+    // Some users deposit into the mixer, each keeping a secret note.
+    // One of them later withdraws to a fresh recipient, proving the note is in the
+    // deposit tree without revealing which deposit is theirs.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 4;
+
+    let mut deposits = DepositTree::<F, H>::new(HEIGHT);
+    let notes = (0..5).map(|_| Note::<F>::new()).collect::<Vec<Note<F>>>();
+    for note in notes.iter() {
+        deposits.deposit(note.commitment::<H>())?;
+    }
+
+    let circuit = WithdrawCircuit::<F, C, H, D>::new(HEIGHT, proof_mode);
+
+    // the contract keeps the set of spent nullifier hashes
+    let mut spent = HashSet::new();
+
+    let note = notes[3];
+    let recipient = HashOut::<F>::rand();
+    let fee = F::from_canonical_u64(10);
+    let proof = circuit.prove(&deposits, &note, 3, recipient, fee)?;
+
+    let public_inputs = [
+        deposits.root().elements.to_vec(),
+        note.nullifier_hash::<H>().elements.to_vec(),
+        recipient.elements.to_vec(),
+        vec![fee],
+    ]
+    .concat();
+    ensure!(proof.public_inputs == public_inputs, "unexpected public inputs");
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof.clone())?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    ensure!(spent.insert(proof.public_inputs[4..8].to_vec()), "note already spent");
+    println!("Withdrawal verified, nullifier hash: {:?}", &proof.public_inputs[4..8]);
+
+    // withdrawing the same note again gives a valid proof with a spent nullifier hash
+    let proof = circuit.prove(&deposits, &note, 3, HashOut::<F>::rand(), fee)?;
+    circuit.data.verify(proof.clone())?;
+    ensure!(!spent.insert(proof.public_inputs[4..8].to_vec()));
+    println!("Second withdrawal of the same note rejected");
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use anyhow::{ensure, Result};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2_examples::{
+    compress::proof_sizes,
+    recursion::ProofTuple,
+    statement::{Statement, StatementCircuit},
+    whitelist::{Whitelist, WhitelistCircuit},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // One verifier key for proofs of several circuits, the allowed ones being the leaves
+    // of a public Merkle root. Adding a circuit changes the root, not the verifier key.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 3;
+
+    // statements of one shape: same gates, degree and number of public inputs
+    let statements = [
+        ("a * b = c", r#"{ "a": 17, "b": 19, "c": 323 }"#),
+        ("a + b = c", r#"{ "a": 17, "b": 19, "c": 36 }"#),
+        ("a - b = c", r#"{ "a": 17, "b": 19, "c": -2 }"#),
+    ];
+    let mut children = Vec::new();
+    for (statement, witness) in statements {
+        let json = format!(r#"{{ "statement": "{}", "public": ["c"] }}"#, statement);
+        let circuit =
+            StatementCircuit::<F, C, D>::new(Statement::from_json(&json)?, ProofMode::Standard);
+        let proof = circuit.prove(witness)?;
+        children.push(ProofTuple::leaf(proof, &circuit.data));
+    }
+    let shape = children[0].cd.clone();
+    ensure!(
+        children.iter().all(|child| child.cd == shape),
+        "statements differ in shape"
+    );
+
+    // the first two circuits are allowed
+    let digests = children
+        .iter()
+        .map(|child| child.vd.circuit_digest)
+        .collect::<Vec<_>>();
+    let whitelist = Whitelist::new(&digests[..2], HEIGHT)?;
+
+    let tmp = Instant::now();
+    let circuit = WhitelistCircuit::<F, C, D>::new(&shape, 0, HEIGHT, proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    for (child, (statement, _)) in children[..2].iter().zip(statements.iter()) {
+        let tuple = circuit.prove(child, &whitelist)?;
+        let mut expected = whitelist.root().elements.to_vec();
+        expected.extend(child.statement());
+        ensure!(tuple.statement() == expected, "unexpected public inputs");
+        ensure!(tuple.depth == 1 && tuple.leaves() == 1);
+        println!(
+            "Proof size: {}",
+            proof_sizes(&tuple.proof, &tuple.verifier_data())?
+        );
+        tuple.verifier_data().verify(tuple.proof)?;
+        println!("Proof of {} verified under the whitelist", statement);
+    }
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+
+    // the third is not, until it is added to the whitelist
+    match circuit.prove(&children[2], &whitelist) {
+        Ok(_) => println!("Unexpected: a circuit outside the whitelist was accepted"),
+        Err(e) => println!("Rejected: {}", e),
+    }
+    let whitelist = Whitelist::new(&digests, HEIGHT)?;
+    let tuple = circuit.prove(&children[2], &whitelist)?;
+    ensure!(tuple.proof.public_inputs[..4] == whitelist.root().elements);
+    circuit.data.verify(tuple.proof)?;
+    println!(
+        "Proof of {} verified under the new whitelist root, same verifier key",
+        statements[2].0
+    );
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Sample,
+    hash::hash_types::HashOut,
+    plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+};
+use plonky2_examples::{
+    compress::proof_sizes,
+    winternitz::{self, WotsCircuit, WotsSecretKey},
+    xmss::{self, XmssCircuit, XmssSigner},
+    zk::ProofMode,
+};
+use std::time::Instant;
+
+fn main() -> Result<()> {
+    // We have a public message digest m and a hash-based public key.
+    // Proof that "I know a signature of m under this key", where the signature scheme
+    // uses nothing but Poseidon: first a Winternitz one-time key, then an XMSS-style
+    // Merkle tree of one-time keys that can sign many messages.
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    let proof_mode = ProofMode::from_args();
+
+    const HEIGHT: usize = 4;
+
+    // Winternitz one-time signature
+    let sk = WotsSecretKey::from_seed(HashOut::<F>::rand(), 0);
+    let pk = sk.to_public();
+    let msg = HashOut::<F>::rand();
+    let sig = sk.sign(msg);
+    ensure!(winternitz::verify(msg, &sig, pk));
+    ensure!(!winternitz::verify(HashOut::<F>::rand(), &sig, pk));
+
+    let tmp = Instant::now();
+    let circuit = WotsCircuit::<F, C, D>::new(proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    let proof = circuit.prove(msg, &sig, pk)?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+    ensure!(proof.public_inputs == [msg.elements, pk.elements].concat());
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!("Verified a {} proof", ProofMode::of(&circuit.data.common));
+    println!("Winternitz signature verified");
+
+    // XMSS: 2^HEIGHT one-time keys under one root
+    let tmp = Instant::now();
+    let mut signer = XmssSigner::<F>::new(HashOut::rand(), HEIGHT);
+    let root = signer.public_key();
+    println!(
+        "Generated {} one-time keys in {}ms",
+        signer.remaining(),
+        tmp.elapsed().as_millis()
+    );
+
+    let messages = (0..3).map(|_| HashOut::<F>::rand()).collect::<Vec<_>>();
+    let signatures = messages
+        .iter()
+        .map(|&msg| signer.sign(msg))
+        .collect::<Result<Vec<_>>>()?;
+    for (msg, sig) in messages.iter().zip(signatures.iter()) {
+        ensure!(xmss::verify(*msg, sig, root));
+    }
+    println!("{} one-time keys left", signer.remaining());
+
+    let tmp = Instant::now();
+    let circuit = XmssCircuit::<F, C, D>::new(HEIGHT, proof_mode);
+    println!("Circuit degree: {}", circuit.data.common.degree());
+    let proof = circuit.prove(messages[2], &signatures[2], root)?;
+    println!("Proving took {}ms", tmp.elapsed().as_millis());
+    ensure!(proof.public_inputs == [messages[2].elements, root.elements].concat());
+    println!("Proof size: {}", proof_sizes(&proof, &circuit.data.verifier_data())?);
+    circuit.data.verify(proof)?;
+    println!(
+        "XMSS signature of leaf {} verified",
+        signatures[2].leaf_index
+    );
+
+    // a signature does not carry over to another message
+    ensure!(circuit.prove(messages[0], &signatures[2], root).is_err());
+    println!("Signature of another message rejected");
+
+    // once every leaf is used the signer refuses to sign
+    while signer.remaining() > 0 {
+        signer.sign(HashOut::rand())?;
+    }
+    ensure!(signer.sign(msg).is_err());
+    println!("Signer refuses to reuse a one-time key");
+
+    Ok(())
+}
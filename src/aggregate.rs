@@ -0,0 +1,146 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{hash_types::RichField, poseidon::PoseidonHash},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+    },
+};
+
+use crate::recursion::{merged_tree_info, split_tree_info, ProofTuple};
+use crate::zk::ProofMode;
+
+// Aggregation of proofs from different circuits, e.g. a Schnorr signature proof with an
+// XMSS proof. Every slot is verified against its own child's common data, so the children
+// need not share a config, a degree or a number of public inputs, and no child is padded;
+// the aggregate circuit is as large as the verifiers of all its children together.
+// A merge tree instead expects every node of a layer to have the same shape.
+//
+// For each slot, in order, the public inputs are the child's circuit digest followed by
+// the Poseidon hash of its public inputs. A verifier holding a child's verifier data and
+// statement can check both against its slot. The slots are followed by the depth and
+// leaf count of the aggregate, as for merged proofs.
+
+pub const SLOT_LEN: usize = 8;
+
+/// The public inputs of the slot of `child`.
+pub fn slot_public_inputs<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    child: &ProofTuple<F, C, D>,
+) -> Vec<F>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let public_inputs_hash = PoseidonHash::hash_no_pad(&child.proof.public_inputs);
+    [
+        child.vd.circuit_digest.elements,
+        public_inputs_hash.elements,
+    ]
+    .concat()
+}
+
+/// Verifies all `children` in one proof, in any mix of circuits.
+pub fn aggregate_proofs<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    children: &[ProofTuple<F, C, D>],
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(!children.is_empty(), "nothing to aggregate");
+    // an invalid child would only fail deep inside witness generation
+    for (i, child) in children.iter().enumerate() {
+        ensure!(
+            child.verifier_data().verify(child.proof.clone()).is_ok(),
+            "child {} does not verify",
+            i
+        );
+    }
+
+    let config = proof_mode.config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let mut pw = PartialWitness::new();
+
+    let mut infos = Vec::with_capacity(children.len());
+    for child in children.iter() {
+        let pt = builder.add_virtual_proof_with_pis(&child.cd);
+        let vdt = builder.constant_verifier_data(&child.vd);
+        builder.verify_proof::<C>(&pt, &vdt, &child.cd);
+        pw.set_proof_with_pis_target::<C, D>(&pt, &child.proof);
+
+        let public_inputs_hash =
+            builder.hash_n_to_hash_no_pad::<PoseidonHash>(pt.public_inputs.clone());
+        builder.register_public_inputs(&vdt.circuit_digest.elements);
+        builder.register_public_inputs(&public_inputs_hash.elements);
+        infos.push(split_tree_info(&mut builder, child.depth, &pt).1);
+    }
+    let depths = children.iter().map(|t| t.depth).collect::<Vec<u32>>();
+    let tree_info = merged_tree_info(&mut builder, &depths, &infos);
+    builder.register_public_inputs(&tree_info);
+
+    let data = builder.build::<C>();
+    let proof = data.prove(pw)?;
+    Ok(ProofTuple {
+        proof,
+        vd: data.verifier_only,
+        cd: data.common,
+        depth: children.iter().map(|t| t.depth).max().unwrap() + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use crate::merkle_subset::SubsetCircuit;
+    use crate::pow5::Pow5Circuit;
+    use crate::recursion::{merge_proofs, ChainPolicy};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn aggregate_round_trip() -> Result<()> {
+        let pow5 = Pow5Circuit::<F, C, D>::new(ProofMode::Standard);
+        let subset = SubsetCircuit::<F, C, PoseidonHash, D>::new(ProofMode::Standard);
+        let x = F::from_canonical_u64(3);
+        let steps = [
+            ProofTuple::leaf(pow5.prove(x)?, &pow5.data),
+            ProofTuple::leaf(pow5.prove(x * F::from_canonical_u64(5))?, &pow5.data),
+        ];
+        let chain = merge_proofs(&ChainPolicy { width: 1 }, &steps, ProofMode::Standard)?;
+        let leaf = [F::ONE, F::TWO, F::ZERO, F::ZERO];
+        let subset_proof = ProofTuple::leaf(subset.prove(&leaf, &leaf)?, &subset.data);
+
+        // children of other circuits and depths, in any order
+        let children = [chain, subset_proof];
+        let aggregate = aggregate_proofs(&children, ProofMode::Standard)?;
+        let public_inputs = &aggregate.proof.public_inputs;
+        assert_eq!(public_inputs.len(), 2 * SLOT_LEN + 2);
+        for (i, child) in children.iter().enumerate() {
+            assert_eq!(
+                public_inputs[i * SLOT_LEN..(i + 1) * SLOT_LEN],
+                slot_public_inputs(child)
+            );
+        }
+        assert_eq!(aggregate.depth, 2);
+        assert_eq!(aggregate.leaves(), 3);
+        aggregate.verifier_data().verify(aggregate.proof.clone())?;
+
+        let mut tampered = children[1].clone();
+        tampered.proof.public_inputs[0] += F::ONE;
+        assert!(aggregate_proofs(&[tampered], ProofMode::Standard).is_err());
+        assert!(aggregate_proofs::<F, C, D>(&[], ProofMode::Standard).is_err());
+        Ok(())
+    }
+}
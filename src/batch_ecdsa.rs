@@ -0,0 +1,171 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{extension::Extendable, secp256k1_scalar::Secp256K1Scalar},
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::target::Target,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+    },
+};
+use plonky2_ecdsa::curve::ecdsa::{ECDSAPublicKey, ECDSASignature};
+
+use crate::ecdsa::{pk_to_limbs, to_limbs, Curve, EcdsaCircuit};
+use crate::recursion::{merge_tree, MergePolicy, ProofTuple};
+use crate::zk::{ProofMode, PublicKeyMode};
+
+// Many ECDSA proofs are merged pairwise into one proof whose public input is
+// a Poseidon tree digest over all signed (message, public key) pairs.
+// Leaf proofs have 24 public inputs (message, pk), merged proofs have 4 (the digest).
+
+// generates the ground proof for one signature
+pub fn ground_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    circuit: &EcdsaCircuit<F, C, D>,
+    msg: Secp256K1Scalar,
+    sig: ECDSASignature<Curve>,
+    pk: &ECDSAPublicKey<Curve>,
+) -> Result<ProofTuple<F, C, D>> {
+    ensure!(
+        circuit.mode == PublicKeyMode::Exposed,
+        "batching needs the public key in the public inputs"
+    );
+    let proof = circuit.prove(msg, sig, pk, None)?;
+    Ok(ProofTuple::leaf(proof, &circuit.data))
+}
+
+/// Merges proofs, each being either a signature proof or an earlier merge, into one
+/// exposing the pairwise Poseidon tree over their digests. The digest of a child is its
+/// public inputs, hashed first if the child is a signature proof, i.e. of depth 0.
+/// Pairing the digests inside a merge gives the same root for any power of two arity.
+pub struct BatchDigestPolicy;
+
+fn child_digest<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    depth: u32,
+    public_inputs: &[Target],
+) -> HashOutTarget {
+    if depth == 0 {
+        builder.hash_n_to_hash_no_pad::<PoseidonHash>(public_inputs.to_vec())
+    } else {
+        assert_eq!(public_inputs.len(), 4, "a merged proof states one digest");
+        HashOutTarget::from_vec(public_inputs.to_vec())
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> MergePolicy<F, D> for BatchDigestPolicy {
+    fn merge(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        depths: &[u32],
+        children: &[&[Target]],
+    ) -> Vec<Target> {
+        assert!(children.len().is_power_of_two());
+        let mut digests = depths
+            .iter()
+            .zip(children.iter())
+            .map(|(&depth, c)| child_digest(builder, depth, c))
+            .collect::<Vec<HashOutTarget>>();
+        while digests.len() > 1 {
+            digests = digests
+                .chunks(2)
+                .map(|pair| {
+                    let inputs = [pair[0].elements, pair[1].elements].concat();
+                    builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs)
+                })
+                .collect();
+        }
+        digests[0].elements.to_vec()
+    }
+}
+
+// this function merges the signature proofs into one, `arity` at a time, their number must
+// be a power of the arity, itself a power of two.
+// The root merge is made in `proof_mode`, the ones below it in standard mode.
+pub fn recursive_tree<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    proofs: &[ProofTuple<F, C, D>],
+    arity: usize,
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(arity.is_power_of_two(), "arity must be a power of two");
+    merge_tree(&BatchDigestPolicy, proofs, arity, proof_mode)
+}
+
+// native helpers
+
+// digest of a single signed pair, equal to the hash of a signature proof's public inputs
+pub fn pair_digest<F: RichField>(msg: Secp256K1Scalar, pk: &ECDSAPublicKey<Curve>) -> HashOut<F> {
+    let mut inputs = to_limbs(msg);
+    inputs.extend(pk_to_limbs::<F>(pk));
+    PoseidonHash::hash_no_pad(&inputs)
+}
+
+// the value the root of `recursive_tree` exposes for these pairs, whose number must be a
+// power of two
+pub fn batch_digest<F: RichField>(
+    pairs: &[(Secp256K1Scalar, ECDSAPublicKey<Curve>)],
+) -> Result<HashOut<F>> {
+    ensure!(
+        pairs.len() >= 2 && pairs.len().is_power_of_two(),
+        "number of pairs must be a power of two, at least 2, got {}",
+        pairs.len()
+    );
+    let mut digests = pairs
+        .iter()
+        .map(|(msg, pk)| pair_digest(*msg, pk))
+        .collect::<Vec<HashOut<F>>>();
+    while digests.len() > 1 {
+        digests = digests
+            .chunks(2)
+            .map(|pair| {
+                let inputs = [pair[0].elements, pair[1].elements].concat();
+                PoseidonHash::hash_no_pad(&inputs)
+            })
+            .collect();
+    }
+    Ok(digests[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{field::types::Sample, plonk::config::PoseidonGoldilocksConfig};
+
+    use crate::ecdsa::{keygen, sign};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn batch_round_trip() -> Result<()> {
+        let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, ProofMode::Standard);
+        let mut pairs = Vec::new();
+        let mut proofs = Vec::new();
+        for _ in 0..2 {
+            let (sk, pk) = keygen();
+            let msg = Secp256K1Scalar::rand();
+            proofs.push(ground_proof(&circuit, msg, sign(msg, sk), &pk)?);
+            pairs.push((msg, pk));
+        }
+        // reusing the two signature proofs keeps the test to two ECDSA proofs
+        let order = [0, 1, 1, 0];
+        let proofs = order.map(|i| proofs[i].clone());
+        let pairs = order.map(|i| pairs[i]);
+
+        let root = recursive_tree(&proofs, 2, ProofMode::Standard)?;
+        assert_eq!(root.statement(), batch_digest::<F>(&pairs)?.elements);
+        assert_eq!(root.leaves(), 4);
+        root.verifier_data().verify(root.proof)?;
+
+        assert!(recursive_tree(&proofs, 3, ProofMode::Standard).is_err());
+        assert!(batch_digest::<F>(&pairs[..3]).is_err());
+        assert!(batch_digest::<F>(&pairs[..1]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, RichField},
+        merkle_tree::MerkleTree,
+    },
+    plonk::config::{AlgebraicHasher, GenericConfig},
+};
+
+use crate::merkle_update::MerkleUpdateCircuit;
+use crate::recursion::{is_power_of, merge_tree, ChainPolicy, ProofTuple};
+use crate::zk::ProofMode;
+
+// K leaf updates applied one after the other, each proven by a `MerkleUpdateCircuit`
+// proof with public inputs (old_root, new_root). Merging two proofs requires the
+// new root of the first to be the old root of the second, so the final proof
+// exposes only the initial and the final root.
+
+// proves every update, leaving `tree` in its final state
+pub fn ground_proofs<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+>(
+    circuit: &MerkleUpdateCircuit<F, C, H, D>,
+    tree: &mut MerkleTree<F, H>,
+    updates: &[(usize, HashOut<F>)],
+) -> Result<Vec<ProofTuple<F, C, D>>> {
+    let mut proofs = Vec::new();
+    for &(leaf_index, new_leaf) in updates.iter() {
+        let (proof, new_tree) = circuit.prove(tree, leaf_index, new_leaf)?;
+        proofs.push(ProofTuple::leaf(proof, &circuit.data));
+        *tree = new_tree;
+    }
+    Ok(proofs)
+}
+
+// this function merges the update proofs in order, `arity` at a time, their number must be
+// a power of the arity. 0..4 of the public inputs is "old_root" and 4..8 is "new_root",
+// chained like any state.
+pub fn recursive_tree<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    proofs: &[ProofTuple<F, C, D>],
+    arity: usize,
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(
+        is_power_of(proofs.len(), arity),
+        "number of updates must be a power of the arity, got {} updates for arity {}",
+        proofs.len(),
+        arity
+    );
+    merge_tree(&ChainPolicy { width: 4 }, proofs, arity, proof_mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::{Field, Sample},
+        hash::poseidon::PoseidonHash,
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    const HEIGHT: usize = 2;
+
+    #[test]
+    fn batch_round_trip() -> Result<()> {
+        let leaves = (0..1 << HEIGHT)
+            .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+            .collect::<Vec<Vec<F>>>();
+        let tree = MerkleTree::<F, H>::new(leaves, 0);
+        let updates = [1, 2, 1, 3].map(|i| (i, HashOut::<F>::rand()));
+
+        let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+        let mut final_tree = tree.clone();
+        let proofs = ground_proofs(&circuit, &mut final_tree, &updates)?;
+
+        let expected = [tree.cap.0[0].elements, final_tree.cap.0[0].elements].concat();
+        for arity in [2, 4] {
+            let root = recursive_tree(&proofs, arity, ProofMode::Standard)?;
+            assert_eq!(root.statement(), expected);
+            assert_eq!(root.leaves(), 4);
+            root.verifier_data().verify(root.proof)?;
+        }
+
+        assert!(recursive_tree(&proofs[..3], 2, ProofMode::Standard).is_err());
+        assert!(recursive_tree(&proofs, 3, ProofMode::Standard).is_err());
+        Ok(())
+    }
+}
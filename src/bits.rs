@@ -0,0 +1,126 @@
+use num::BigUint;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::target::{BoolTarget, Target},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+// Bitwise gadgets on little-endian BoolTargets, shared by the Keccak and SHA-256 circuits
+// and the signature schemes.
+// Constant inputs are folded, so padding and zero-filled shifts cost nothing.
+
+pub fn xor<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: BoolTarget,
+    b: BoolTarget,
+) -> BoolTarget {
+    for (x, y) in [(a, b), (b, a)] {
+        if let Some(c) = builder.target_as_constant(x.target) {
+            return if c.is_zero() { y } else { builder.not(y) };
+        }
+    }
+    // a + b - 2ab
+    let sum = builder.add(a.target, b.target);
+    let neg_two = -F::TWO;
+    BoolTarget::new_unsafe(builder.arithmetic(neg_two, F::ONE, a.target, b.target, sum))
+}
+
+// also range checks x to 32 bits
+pub fn u32_to_bits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: U32Target,
+) -> Vec<BoolTarget> {
+    builder.split_le(x.0, 32)
+}
+
+pub fn bits_to_u32<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    bits: &[BoolTarget],
+) -> U32Target {
+    assert_eq!(bits.len(), 32);
+    U32Target(builder.le_sum(bits.iter()))
+}
+
+/// The 64 bits of the canonical form of x. `split_le` alone also accepts the bits of x + p
+/// when that fits in 64 bits.
+pub fn split_canonical<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: Target,
+) -> Vec<BoolTarget> {
+    let bits = builder.split_le(x, 64);
+    // x < p: when the high half is all ones, the low half is zero
+    let low = builder.le_sum(bits[..32].iter());
+    let high = builder.le_sum(bits[32..].iter());
+    let max = builder.constant(F::from_canonical_u32(u32::MAX));
+    let high_is_max = builder.is_equal(high, max);
+    let t = builder.mul(high_is_max.target, low);
+    builder.assert_zero(t);
+    bits
+}
+
+/// Asserts that the little-endian `bits` encode an integer less than the constant `bound`.
+pub fn assert_less_than_constant<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    bits: &[BoolTarget],
+    bound: &BigUint,
+) {
+    if bound.bits() > bits.len() as u64 {
+        return;
+    }
+    // from the top bit: less once a bit is below the bound's while all above are equal
+    let mut less = builder._false();
+    let mut equal = builder._true();
+    for (i, &bit) in bits.iter().enumerate().rev() {
+        let not_bit = builder.not(bit);
+        if bound.bit(i as u64) {
+            let below = builder.and(equal, not_bit);
+            less = builder.or(less, below);
+            equal = builder.and(equal, bit);
+        } else {
+            equal = builder.and(equal, not_bit);
+        }
+    }
+    builder.assert_one(less.target);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn prove_less_than(x: u64, bound: u64) -> anyhow::Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x_target = builder.add_virtual_target();
+        let bits = builder.split_le(x_target, 8);
+        assert_less_than_constant(&mut builder, &bits, &BigUint::from(bound));
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x_target, F::from_canonical_u64(x));
+        data.verify(data.prove(pw)?)
+    }
+
+    #[test]
+    fn less_than_constant() {
+        for x in [0, 1, 99, 100, 101, 255] {
+            if x < 100 {
+                prove_less_than(x, 100).unwrap();
+            } else {
+                let result = std::panic::catch_unwind(|| prove_less_than(x, 100));
+                assert!(!matches!(result, Ok(Ok(()))), "{} < 100 was accepted", x);
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    plonk::{
+        circuit_data::VerifierCircuitData,
+        config::GenericConfig,
+        proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs},
+    },
+};
+
+// Compressed proofs as a storage and wire format. Compression drops the Merkle path nodes
+// that several FRI queries share and the values the verifier can recompute from the
+// challenges, so both directions need the circuit digest and common data.
+// Decompressing here always verifies, so bytes read back are either a valid proof or an
+// error.
+
+/// The compressed serialization of a proof.
+pub fn compress_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    proof: &ProofWithPublicInputs<F, C, D>,
+    verifier: &VerifierCircuitData<F, C, D>,
+) -> Result<Vec<u8>> {
+    let compressed = proof
+        .clone()
+        .compress(&verifier.verifier_only.circuit_digest, &verifier.common)?;
+    Ok(compressed.to_bytes())
+}
+
+/// Parses and verifies the output of `compress_proof`, returning the full proof.
+pub fn decompress_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    bytes: &[u8],
+    verifier: &VerifierCircuitData<F, C, D>,
+) -> Result<ProofWithPublicInputs<F, C, D>> {
+    let compressed =
+        CompressedProofWithPublicInputs::<F, C, D>::from_bytes(bytes.to_vec(), &verifier.common)?;
+    verifier.verify_compressed(compressed.clone())?;
+    compressed.decompress(&verifier.verifier_only.circuit_digest, &verifier.common)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofSizes {
+    pub raw: usize,
+    pub compressed: usize,
+}
+
+impl fmt::Display for ProofSizes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes raw, {} bytes compressed",
+            self.raw, self.compressed
+        )
+    }
+}
+
+/// Both serialized sizes of a proof, after checking that the compressed form decompresses
+/// back to it and verifies.
+pub fn proof_sizes<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    proof: &ProofWithPublicInputs<F, C, D>,
+    verifier: &VerifierCircuitData<F, C, D>,
+) -> Result<ProofSizes> {
+    let bytes = compress_proof(proof, verifier)?;
+    let decompressed = decompress_proof(&bytes, verifier)?;
+    ensure!(
+        decompressed == *proof,
+        "decompressed proof differs from the original"
+    );
+    Ok(ProofSizes {
+        raw: proof.to_bytes().len(),
+        compressed: bytes.len(),
+    })
+}
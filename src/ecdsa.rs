@@ -0,0 +1,274 @@
+use anyhow::Result;
+use plonky2::{
+    field::{
+        extension::Extendable,
+        secp256k1_scalar::Secp256K1Scalar,
+        types::{PrimeField, Sample},
+    },
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_ecdsa::{
+    curve::{
+        curve_types::AffinePoint,
+        ecdsa::{sign_message, ECDSAPublicKey, ECDSASecretKey, ECDSASignature},
+        secp256k1::Secp256K1,
+    },
+    gadgets::{
+        biguint::{BigUintTarget, CircuitBuilderBiguint, WitnessBigUint},
+        curve::AffinePointTarget,
+        ecdsa::{verify_message_circuit, ECDSAPublicKeyTarget, ECDSASignatureTarget},
+        nonnative::{CircuitBuilderNonNative, NonNativeTarget},
+    },
+};
+
+use crate::zk::{ProofMode, PublicKeyMode};
+
+pub type Curve = Secp256K1;
+
+// secp256k1 field elements are 256 bits, stored as 8 u32 limbs in the circuit
+pub const NUM_LIMBS: usize = 8;
+
+// Public inputs are laid out as follows:
+// 0..8 is the message hash, then either 8..24 is (pk.x, pk.y) or 8..12 is the commitment.
+pub struct EcdsaTargets {
+    pub msg: BigUintTarget,
+    pub pk_x: BigUintTarget,
+    pub pk_y: BigUintTarget,
+    pub r: BigUintTarget,
+    pub s: BigUintTarget,
+    pub blinding: Option<HashOutTarget>,
+}
+
+// Adds a non-native witness value whose limbs are range checked and which is
+// checked to be in reduced form, as `connect_nonnative` assumes.
+fn add_virtual_reduced<F: RichField + Extendable<D>, const D: usize, FF: PrimeField>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> (NonNativeTarget<FF>, BigUintTarget) {
+    let x = builder.add_virtual_nonnative_target::<FF>();
+    let limbs = builder.nonnative_to_canonical_biguint(&x);
+    for limb in limbs.limbs.iter() {
+        builder.range_check(limb.0, 32);
+    }
+    let max = builder.constant_biguint(&(FF::order() - 1u32));
+    let reduced = builder.cmp_biguint(&limbs, &max);
+    builder.assert_one(reduced.target);
+    (x, limbs)
+}
+
+// Poseidon commitment to a public key, computed inside the circuit.
+pub fn pk_commitment_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    pk_x: &BigUintTarget,
+    pk_y: &BigUintTarget,
+    blinding: HashOutTarget,
+) -> HashOutTarget {
+    let inputs = pk_x
+        .limbs
+        .iter()
+        .chain(pk_y.limbs.iter())
+        .map(|limb| limb.0)
+        .chain(blinding.elements)
+        .collect::<Vec<Target>>();
+    builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs)
+}
+
+/// Builds the constraints "sig is a valid signature of msg under pk" and registers
+/// the public inputs according to `mode`.
+pub fn verify_signature_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    mode: PublicKeyMode,
+) -> EcdsaTargets {
+    let (msg_nn, msg) = add_virtual_reduced::<F, D, Secp256K1Scalar>(builder);
+    let (x_nn, pk_x) = add_virtual_reduced(builder);
+    let (y_nn, pk_y) = add_virtual_reduced(builder);
+    let (r_nn, r) = add_virtual_reduced(builder);
+    let (s_nn, s) = add_virtual_reduced(builder);
+
+    let pk_target = ECDSAPublicKeyTarget(AffinePointTarget::<Curve> { x: x_nn, y: y_nn });
+    let sig_target = ECDSASignatureTarget { r: r_nn, s: s_nn };
+    verify_message_circuit(builder, msg_nn, sig_target, pk_target);
+
+    let msg_limbs = msg.limbs.iter().map(|limb| limb.0).collect::<Vec<Target>>();
+    builder.register_public_inputs(&msg_limbs);
+
+    let blinding = match mode {
+        PublicKeyMode::Exposed => {
+            for limb in pk_x.limbs.iter().chain(pk_y.limbs.iter()) {
+                builder.register_public_input(limb.0);
+            }
+            None
+        }
+        PublicKeyMode::Committed => {
+            let blinding = builder.add_virtual_hash();
+            let commitment = pk_commitment_circuit(builder, &pk_x, &pk_y, blinding);
+            builder.register_public_inputs(&commitment.elements);
+            Some(blinding)
+        }
+    };
+
+    EcdsaTargets {
+        msg,
+        pk_x,
+        pk_y,
+        r,
+        s,
+        blinding,
+    }
+}
+
+// native helpers
+
+pub fn keygen() -> (ECDSASecretKey<Curve>, ECDSAPublicKey<Curve>) {
+    let sk = ECDSASecretKey::<Curve>(Secp256K1Scalar::rand());
+    let pk = sk.to_public();
+    (sk, pk)
+}
+
+pub fn sign(msg: Secp256K1Scalar, sk: ECDSASecretKey<Curve>) -> ECDSASignature<Curve> {
+    sign_message(msg, sk)
+}
+
+// splits a 256-bit value into the 8 limbs used for it in the circuit
+pub fn to_limbs<F: RichField, FF: PrimeField>(x: FF) -> Vec<F> {
+    let mut limbs = x.to_canonical_biguint().to_u32_digits();
+    limbs.resize(NUM_LIMBS, 0);
+    limbs.into_iter().map(F::from_canonical_u32).collect()
+}
+
+pub fn pk_to_limbs<F: RichField>(pk: &ECDSAPublicKey<Curve>) -> Vec<F> {
+    let AffinePoint { x, y, .. } = pk.0;
+    let mut limbs = to_limbs(x);
+    limbs.extend(to_limbs::<F, _>(y));
+    limbs
+}
+
+// Native counterpart of `pk_commitment_circuit`.
+pub fn pk_commitment<F: RichField>(pk: &ECDSAPublicKey<Curve>, blinding: HashOut<F>) -> HashOut<F> {
+    let mut inputs = pk_to_limbs(pk);
+    inputs.extend(blinding.elements);
+    PoseidonHash::hash_no_pad(&inputs)
+}
+
+pub fn set_signature_witness<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &EcdsaTargets,
+    msg: Secp256K1Scalar,
+    sig: ECDSASignature<Curve>,
+    pk: &ECDSAPublicKey<Curve>,
+    blinding: Option<HashOut<F>>,
+) {
+    pw.set_biguint_target(&targets.msg, &msg.to_canonical_biguint());
+    pw.set_biguint_target(&targets.pk_x, &pk.0.x.to_canonical_biguint());
+    pw.set_biguint_target(&targets.pk_y, &pk.0.y.to_canonical_biguint());
+    pw.set_biguint_target(&targets.r, &sig.r.to_canonical_biguint());
+    pw.set_biguint_target(&targets.s, &sig.s.to_canonical_biguint());
+    if let Some(blinding_target) = targets.blinding {
+        let blinding = blinding.expect("a committed public key needs a blinding factor");
+        pw.set_hash_target(blinding_target, blinding);
+    }
+}
+
+pub struct EcdsaCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub mode: PublicKeyMode,
+    pub targets: EcdsaTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> EcdsaCircuit<F, C, D> {
+    pub fn new(mode: PublicKeyMode, proof_mode: ProofMode) -> Self {
+        // non-native arithmetic needs one more wire than the recursion config
+        let config = proof_mode.ecc_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = verify_signature_circuit(&mut builder, mode);
+        let data = builder.build::<C>();
+        EcdsaCircuit { mode, targets, data }
+    }
+
+    pub fn prove(
+        &self,
+        msg: Secp256K1Scalar,
+        sig: ECDSASignature<Curve>,
+        pk: &ECDSAPublicKey<Curve>,
+        blinding: Option<HashOut<F>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let mut pw = PartialWitness::new();
+        set_signature_witness(&mut pw, &self.targets, msg, sig, pk, blinding);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn limbs() {
+        let limbs = to_limbs::<F, _>(Secp256K1Scalar::from_canonical_u64(0x1_0000_0002));
+        assert_eq!(limbs.len(), NUM_LIMBS);
+        assert_eq!(limbs[..3], [2, 1, 0].map(F::from_canonical_u32));
+        let (_, pk) = keygen();
+        assert_eq!(pk_to_limbs::<F>(&pk).len(), 2 * NUM_LIMBS);
+    }
+
+    #[test]
+    fn exposed_key() -> Result<()> {
+        let (sk, pk) = keygen();
+        let msg = Secp256K1Scalar::rand();
+        let sig = sign(msg, sk);
+
+        let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, ProofMode::Standard);
+        let proof = circuit.prove(msg, sig, &pk, None)?;
+        let expected = [to_limbs::<F, _>(msg), pk_to_limbs(&pk)].concat();
+        assert_eq!(proof.public_inputs, expected);
+        circuit.data.verify(proof)?;
+
+        // the signature of another message does not verify
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            circuit.prove(Secp256K1Scalar::rand(), sig, &pk, None)
+        }));
+        assert!(!matches!(result, Ok(Ok(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn committed_key() -> Result<()> {
+        let (sk, pk) = keygen();
+        let msg = Secp256K1Scalar::rand();
+        let sig = sign(msg, sk);
+        let blinding = HashOut::rand();
+
+        let circuit = EcdsaCircuit::<F, C, D>::new(PublicKeyMode::Committed, ProofMode::Standard);
+        let proof = circuit.prove(msg, sig, &pk, Some(blinding))?;
+        let expected = [
+            to_limbs::<F, _>(msg),
+            pk_commitment(&pk, blinding).elements.to_vec(),
+        ]
+        .concat();
+        assert_eq!(proof.public_inputs, expected);
+        circuit.data.verify(proof)?;
+        Ok(())
+    }
+}
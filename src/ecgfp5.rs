@@ -0,0 +1,570 @@
+use num::{BigUint, Zero};
+use plonky2::{
+    field::{
+        extension::{quintic::QuinticExtension, Extendable, FieldExtension},
+        goldilocks_field::GoldilocksField,
+        ops::Square,
+        types::{Field, PrimeField64},
+    },
+    hash::hash_types::RichField,
+    iop::{
+        generator::{GeneratedValues, SimpleGenerator},
+        target::{BoolTarget, Target},
+        witness::{PartitionWitness, Witness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CommonCircuitData},
+    util::serialization::{Buffer, IoResult, Read, Write},
+};
+use rand::RngCore;
+
+// EcGFp5, the curve y^2 = x (x^2 + a x + b) over GF(p^5) = GF(p)[z] / (z^5 - 3),
+// where p is the Goldilocks prime, a = 2 and b = 263 z. Its order is 2 n for a
+// 319-bit prime n, and we work in the subgroup of order n.
+// Field elements are in the circuit's own field, no non-native arithmetic is needed:
+// an element of GF(p^5) is 5 targets, its coefficients of 1, z, .., z^4.
+//
+// Points are affine and the formulas are the incomplete ones, every addition and doubling
+// asserts it is not an exceptional case, so a proof never rests on a wrong formula.
+
+pub type GFp5 = QuinticExtension<GoldilocksField>;
+
+pub const CURVE_A: u64 = 2;
+pub const CURVE_B_Z: u64 = 263;
+
+// the prime order of the subgroup
+pub const ORDER: &str = "1067993516717146951041484916571792702745057740581727230159139685185762082554198619328292418486241";
+
+pub fn order() -> BigUint {
+    ORDER.parse().unwrap()
+}
+
+fn curve_b() -> GFp5 {
+    QuinticExtension([
+        GoldilocksField::ZERO,
+        GoldilocksField::from_canonical_u64(CURVE_B_Z),
+        GoldilocksField::ZERO,
+        GoldilocksField::ZERO,
+        GoldilocksField::ZERO,
+    ])
+}
+
+// x (x^2 + a x + b)
+fn curve_rhs(x: GFp5) -> GFp5 {
+    x * (x * x + GFp5::from_canonical_u64(CURVE_A) * x + curve_b())
+}
+
+// Tonelli-Shanks in GF(p^5), whose multiplicative group has the 2-adicity of GF(p)
+fn sqrt(x: GFp5) -> Option<GFp5> {
+    if x.is_zero() {
+        return Some(x);
+    }
+    let q_minus_one = GFp5::order() - 1u32;
+    if x.exp_biguint(&(&q_minus_one >> 1)) != GFp5::ONE {
+        return None;
+    }
+    let mut v = GFp5::TWO_ADICITY;
+    let t = &q_minus_one >> v;
+    let mut z = GFp5::POWER_OF_TWO_GENERATOR;
+    let mut w = x.exp_biguint(&((t - 1u32) >> 1));
+    let mut root = w * x;
+    let mut b = root * w;
+    while !b.is_one() {
+        let mut k = 0;
+        let mut b2k = b;
+        while !b2k.is_one() {
+            b2k = b2k.square();
+            k += 1;
+        }
+        w = z;
+        for _ in 0..v - k - 1 {
+            w = w.square();
+        }
+        z = w.square();
+        b *= z;
+        root *= w;
+        v = k;
+    }
+    Some(root)
+}
+
+/// An affine point, or the neutral element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub x: GFp5,
+    pub y: GFp5,
+    pub is_zero: bool,
+}
+
+impl Point {
+    pub const ZERO: Point = Point {
+        x: QuinticExtension([GoldilocksField::ZERO; 5]),
+        y: QuinticExtension([GoldilocksField::ZERO; 5]),
+        is_zero: true,
+    };
+
+    pub fn is_on_curve(&self) -> bool {
+        self.is_zero || self.y * self.y == curve_rhs(self.x)
+    }
+
+    /// The point 2 (x, y) of the subgroup for the first x = x0 + k, k = 0, 1, .. on the curve.
+    /// Nobody knows its discrete logarithm with respect to another such point.
+    pub fn hash_to_subgroup(x0: GFp5) -> Point {
+        let mut x = x0;
+        loop {
+            if let Some(y) = sqrt(curve_rhs(x)) {
+                if !y.is_zero() {
+                    return Point {
+                        x,
+                        y,
+                        is_zero: false,
+                    }
+                    .double();
+                }
+            }
+            x += GFp5::ONE;
+        }
+    }
+
+    /// The generator used here: the double of the point with the smallest x in GF(p).
+    /// This is not the generator of the ecgfp5 reference implementation.
+    pub fn generator() -> Point {
+        Point::hash_to_subgroup(GFp5::ONE)
+    }
+
+    pub fn neg(&self) -> Point {
+        Point {
+            x: self.x,
+            y: -self.y,
+            is_zero: self.is_zero,
+        }
+    }
+
+    pub fn double(&self) -> Point {
+        if self.is_zero || self.y.is_zero() {
+            return Point::ZERO;
+        }
+        let a = GFp5::from_canonical_u64(CURVE_A);
+        let x2 = self.x * self.x;
+        let lambda = (x2.triple() + a.double() * self.x + curve_b()) / self.y.double();
+        let x = lambda * lambda - a - self.x.double();
+        let y = lambda * (self.x - x) - self.y;
+        Point {
+            x,
+            y,
+            is_zero: false,
+        }
+    }
+
+    pub fn add(&self, other: &Point) -> Point {
+        if self.is_zero {
+            return *other;
+        }
+        if other.is_zero {
+            return *self;
+        }
+        if self.x == other.x {
+            return if self.y == other.y {
+                self.double()
+            } else {
+                Point::ZERO
+            };
+        }
+        let a = GFp5::from_canonical_u64(CURVE_A);
+        let lambda = (other.y - self.y) / (other.x - self.x);
+        let x = lambda * lambda - a - self.x - other.x;
+        let y = lambda * (self.x - x) - self.y;
+        Point {
+            x,
+            y,
+            is_zero: false,
+        }
+    }
+
+    pub fn mul(&self, scalar: &BigUint) -> Point {
+        let mut result = Point::ZERO;
+        for i in (0..scalar.bits()).rev() {
+            result = result.double();
+            if scalar.bit(i) {
+                result = result.add(self);
+            }
+        }
+        result
+    }
+}
+
+/// A uniformly random scalar in [1, n).
+pub fn random_scalar<R: RngCore>(rng: &mut R) -> BigUint {
+    let n = order();
+    loop {
+        let mut bytes = [0u8; 48];
+        rng.fill_bytes(&mut bytes);
+        let k = BigUint::from_bytes_le(&bytes) % &n;
+        if !k.is_zero() {
+            return k;
+        }
+    }
+}
+
+// coefficients of 1, z, .., z^4
+pub fn gfp5_to_limbs<F: RichField>(x: GFp5) -> [F; 5] {
+    x.to_basefield_array()
+        .map(|c: GoldilocksField| F::from_canonical_u64(c.to_canonical_u64()))
+}
+
+pub fn point_to_limbs<F: RichField>(p: &Point) -> Vec<F> {
+    assert!(!p.is_zero, "the neutral element has no affine coordinates");
+    let mut limbs = gfp5_to_limbs::<F>(p.x).to_vec();
+    limbs.extend(gfp5_to_limbs::<F>(p.y));
+    limbs
+}
+
+// circuit
+
+pub type GFp5Target = [Target; 5];
+
+#[derive(Clone, Copy, Debug)]
+pub struct PointTarget {
+    pub x: GFp5Target,
+    pub y: GFp5Target,
+}
+
+impl PointTarget {
+    pub fn to_vec(&self) -> Vec<Target> {
+        self.x.iter().chain(self.y.iter()).copied().collect()
+    }
+}
+
+pub fn gfp5_constant<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: GFp5,
+) -> GFp5Target {
+    gfp5_to_limbs(x).map(|c| builder.constant(c))
+}
+
+pub fn gfp5_add<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: GFp5Target,
+    b: GFp5Target,
+) -> GFp5Target {
+    core::array::from_fn(|i| builder.add(a[i], b[i]))
+}
+
+pub fn gfp5_sub<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: GFp5Target,
+    b: GFp5Target,
+) -> GFp5Target {
+    core::array::from_fn(|i| builder.sub(a[i], b[i]))
+}
+
+// schoolbook product, reduced with z^5 = 3
+pub fn gfp5_mul<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: GFp5Target,
+    b: GFp5Target,
+) -> GFp5Target {
+    let w = F::from_canonical_u64(3);
+    let mut c = [builder.zero(); 5];
+    for i in 0..5 {
+        for j in 0..5 {
+            let coeff = if i + j < 5 { F::ONE } else { w };
+            c[(i + j) % 5] = builder.arithmetic(coeff, F::ONE, a[i], b[j], c[(i + j) % 5]);
+        }
+    }
+    c
+}
+
+// Fills in the inverse of a non-zero element.
+#[derive(Debug)]
+struct GFp5InverseGenerator {
+    x: GFp5Target,
+    inverse: GFp5Target,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for GFp5InverseGenerator {
+    fn id(&self) -> String {
+        "GFp5InverseGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        self.x.to_vec()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let x = GFp5::from_basefield_array(self.x.map(|t| {
+            GoldilocksField::from_canonical_u64(witness.get_target(t).to_canonical_u64())
+        }));
+        // zero has no inverse, leaving the constraint unsatisfiable
+        let inverse = x.try_inverse().unwrap_or(GFp5::ZERO);
+        for (&t, c) in self.inverse.iter().zip(gfp5_to_limbs::<F>(inverse)) {
+            out_buffer.set_target(t, c);
+        }
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target_vec(&self.x)?;
+        dst.write_target_vec(&self.inverse)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = src.read_target_vec()?.try_into().unwrap();
+        let inverse = src.read_target_vec()?.try_into().unwrap();
+        Ok(GFp5InverseGenerator { x, inverse })
+    }
+}
+
+/// 1 / x, which also asserts x is not zero.
+pub fn gfp5_inverse<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: GFp5Target,
+) -> GFp5Target {
+    let inverse: GFp5Target = builder.add_virtual_targets(5).try_into().unwrap();
+    builder.add_simple_generator(GFp5InverseGenerator { x, inverse });
+    let product = gfp5_mul(builder, x, inverse);
+    builder.assert_one(product[0]);
+    for &c in product[1..].iter() {
+        builder.assert_zero(c);
+    }
+    inverse
+}
+
+pub fn gfp5_select<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    b: BoolTarget,
+    x: GFp5Target,
+    y: GFp5Target,
+) -> GFp5Target {
+    core::array::from_fn(|i| builder.select(b, x[i], y[i]))
+}
+
+pub fn add_virtual_point<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> PointTarget {
+    PointTarget {
+        x: builder.add_virtual_targets(5).try_into().unwrap(),
+        y: builder.add_virtual_targets(5).try_into().unwrap(),
+    }
+}
+
+pub fn point_constant<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &Point,
+) -> PointTarget {
+    assert!(!p.is_zero, "the neutral element has no affine coordinates");
+    PointTarget {
+        x: gfp5_constant(builder, p.x),
+        y: gfp5_constant(builder, p.y),
+    }
+}
+
+pub fn set_point_target<F: RichField, W: WitnessWrite<F>>(
+    witness: &mut W,
+    target: &PointTarget,
+    p: &Point,
+) {
+    witness.set_target_arr(&target.to_vec(), &point_to_limbs(p));
+}
+
+pub fn connect_points<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &PointTarget,
+    q: &PointTarget,
+) {
+    for (a, b) in p.to_vec().into_iter().zip(q.to_vec()) {
+        builder.connect(a, b);
+    }
+}
+
+pub fn assert_on_curve<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &PointTarget,
+) {
+    // y^2 = x (x^2 + a x + b)
+    let x2 = gfp5_mul(builder, p.x, p.x);
+    let a = F::from_canonical_u64(CURVE_A);
+    let mut t: GFp5Target = core::array::from_fn(|i| builder.mul_const_add(a, p.x[i], x2[i]));
+    t[1] = builder.add_const(t[1], F::from_canonical_u64(CURVE_B_Z));
+    let rhs = gfp5_mul(builder, p.x, t);
+    let lhs = gfp5_mul(builder, p.y, p.y);
+    for (a, b) in lhs.into_iter().zip(rhs) {
+        builder.connect(a, b);
+    }
+}
+
+pub fn point_neg<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &PointTarget,
+) -> PointTarget {
+    PointTarget {
+        x: p.x,
+        y: p.y.map(|c| builder.neg(c)),
+    }
+}
+
+// x3 = lambda^2 - a - x1 - x2, y3 = lambda (x1 - x3) - y1
+fn chord<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lambda: GFp5Target,
+    p: &PointTarget,
+    x2: GFp5Target,
+) -> PointTarget {
+    let lambda2 = gfp5_mul(builder, lambda, lambda);
+    let mut x = gfp5_sub(builder, lambda2, p.x);
+    x = gfp5_sub(builder, x, x2);
+    x[0] = builder.add_const(x[0], -F::from_canonical_u64(CURVE_A));
+    let dx = gfp5_sub(builder, p.x, x);
+    let y = gfp5_mul(builder, lambda, dx);
+    let y = gfp5_sub(builder, y, p.y);
+    PointTarget { x, y }
+}
+
+/// p + q, asserting p.x != q.x.
+pub fn point_add<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &PointTarget,
+    q: &PointTarget,
+) -> PointTarget {
+    let dy = gfp5_sub(builder, q.y, p.y);
+    let dx = gfp5_sub(builder, q.x, p.x);
+    let dx_inverse = gfp5_inverse(builder, dx);
+    let lambda = gfp5_mul(builder, dy, dx_inverse);
+    chord(builder, lambda, p, q.x)
+}
+
+/// 2 p, asserting p.y != 0, which holds for every point of odd order.
+pub fn point_double<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    p: &PointTarget,
+) -> PointTarget {
+    // (3 x^2 + 2 a x + b) / 2 y
+    let x2 = gfp5_mul(builder, p.x, p.x);
+    let mut numerator: GFp5Target = core::array::from_fn(|i| {
+        let t = builder.mul_const(F::from_canonical_u64(3), x2[i]);
+        builder.mul_const_add(F::from_canonical_u64(2 * CURVE_A), p.x[i], t)
+    });
+    numerator[1] = builder.add_const(numerator[1], F::from_canonical_u64(CURVE_B_Z));
+    let y2 = gfp5_add(builder, p.y, p.y);
+    let y2_inverse = gfp5_inverse(builder, y2);
+    let lambda = gfp5_mul(builder, numerator, y2_inverse);
+    chord(builder, lambda, p, p.x)
+}
+
+pub fn point_select<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    b: BoolTarget,
+    p: &PointTarget,
+    q: &PointTarget,
+) -> PointTarget {
+    PointTarget {
+        x: gfp5_select(builder, b, p.x, q.x),
+        y: gfp5_select(builder, b, p.y, q.y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Sample,
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use crate::factor::is_probable_prime;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn subgroup_order() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let n = order();
+        assert_eq!(n.bits(), 319);
+        assert!(is_probable_prime(&n, 20, &mut rng));
+
+        let g = Point::generator();
+        assert!(g.is_on_curve());
+        assert!(!g.is_zero);
+        assert_eq!(g.mul(&n), Point::ZERO);
+        assert_eq!(g.mul(&(&n - 1u32)), g.neg());
+
+        // (0, 0) is the point of order two, outside the subgroup
+        let t = Point {
+            is_zero: false,
+            ..Point::ZERO
+        };
+        assert!(t.is_on_curve());
+        assert_eq!(t.double(), Point::ZERO);
+        assert_ne!(t.mul(&n), Point::ZERO);
+    }
+
+    #[test]
+    fn group_laws() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let g = Point::generator();
+        let (a, b) = (random_scalar(&mut rng), random_scalar(&mut rng));
+        let (p, q) = (g.mul(&a), g.mul(&b));
+        assert!(p.is_on_curve() && q.is_on_curve());
+        assert_eq!(p.add(&q), q.add(&p));
+        assert_eq!(p.add(&q), g.mul(&((&a + &b) % order())));
+        assert_eq!(p.add(&p), p.double());
+        assert_eq!(p.add(&p.neg()), Point::ZERO);
+        assert_eq!(p.add(&Point::ZERO), p);
+        assert_eq!(p.add(&q).add(&g), p.add(&q.add(&g)));
+    }
+
+    #[test]
+    fn sqrt_of_squares() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        for _ in 0..8 {
+            let x = GFp5::sample(&mut rng);
+            let root = sqrt(x * x).unwrap();
+            assert!(root == x || root == -x);
+        }
+        // 7 generates GF(p)*, so it is not a square there nor, the degree being odd, in GF(p^5)
+        assert!(sqrt(GFp5::from_canonical_u64(7)).is_none());
+    }
+
+    // proves that the circuit's p + q, 2 p and -p match the native ones
+    fn prove_point_ops(p: &Point, q: &Point) -> anyhow::Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let (p_target, q_target) = (
+            add_virtual_point(&mut builder),
+            add_virtual_point(&mut builder),
+        );
+        assert_on_curve(&mut builder, &p_target);
+        assert_on_curve(&mut builder, &q_target);
+        let sum = point_add(&mut builder, &p_target, &q_target);
+        let double = point_double(&mut builder, &p_target);
+        let neg = point_neg(&mut builder, &p_target);
+        for (target, expected) in [(sum, p.add(q)), (double, p.double()), (neg, p.neg())] {
+            let expected = point_constant(&mut builder, &expected);
+            connect_points(&mut builder, &target, &expected);
+        }
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        set_point_target(&mut pw, &p_target, p);
+        set_point_target(&mut pw, &q_target, q);
+        data.verify(data.prove(pw)?)
+    }
+
+    #[test]
+    fn circuit_matches_native() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let g = Point::generator();
+        let p = g.mul(&random_scalar(&mut rng));
+        let q = g.mul(&random_scalar(&mut rng));
+        prove_point_ops(&p, &q).unwrap();
+
+        // p + (-p) is an exceptional case of the incomplete formulas
+        let result = std::panic::catch_unwind(|| prove_point_ops(&p, &p.neg()));
+        assert!(!matches!(result, Ok(Ok(()))), "p + (-p) was accepted");
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{ensure, Result};
+use num::{BigUint, One, Zero};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::witness::PartialWitness,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_ecdsa::gadgets::biguint::{BigUintTarget, CircuitBuilderBiguint, WitnessBigUint};
+use plonky2_u32::gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target};
+use rand::RngCore;
+
+use crate::zk::ProofMode;
+
+// Proof of a non-trivial factorization of a public integer n, equation.rs beyond the
+// field size: "I know a and b such that a * b = n and 1 < a, b", which also gives a, b < n.
+// Integers are BigUintTargets of 32-bit limbs, least significant first.
+//
+// Public inputs are the limbs of n.
+
+// most addends a U32AddManyGate takes
+const MAX_ADDENDS: usize = 16;
+
+pub fn num_limbs(bits: usize) -> usize {
+    bits.div_ceil(32)
+}
+
+/// A biguint target with every limb range checked to 32 bits.
+/// The biguint gadgets assume u32 limbs but do not check limbs they are given.
+pub fn add_virtual_biguint_checked<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    num_limbs: usize,
+) -> BigUintTarget {
+    let x = builder.add_virtual_biguint_target(num_limbs);
+    for limb in x.limbs.iter() {
+        builder.range_check(limb.0, 32);
+    }
+    x
+}
+
+/// a * b with a + b limbs. Unlike `mul_biguint`, which feeds a whole column of partial
+/// products into one add-many gate, columns are summed 16 limbs at a time, the gate's
+/// maximum, so it stays sound for large operands.
+pub fn mul_biguint_exact<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &BigUintTarget,
+    b: &BigUintTarget,
+) -> BigUintTarget {
+    let num_limbs = a.num_limbs() + b.num_limbs();
+    let mut columns = vec![Vec::new(); num_limbs];
+    for (i, &x) in a.limbs.iter().enumerate() {
+        for (j, &y) in b.limbs.iter().enumerate() {
+            let (low, high) = builder.mul_u32(x, y);
+            columns[i + j].push(low);
+            columns[i + j + 1].push(high);
+        }
+    }
+
+    let mut limbs = Vec::with_capacity(num_limbs);
+    for i in 0..num_limbs {
+        let mut summands = std::mem::take(&mut columns[i]);
+        // sum 16 at a time, every partial sum carries into the next column
+        while summands.len() > 1 {
+            let chunk = summands
+                .drain(..summands.len().min(MAX_ADDENDS))
+                .collect::<Vec<U32Target>>();
+            let (sum, carry) = builder.add_many_u32(&chunk);
+            summands.push(sum);
+            if i + 1 < num_limbs {
+                columns[i + 1].push(carry);
+            } else {
+                builder.assert_zero_u32(carry);
+            }
+        }
+        limbs.push(summands.pop().unwrap_or_else(|| builder.zero_u32()));
+    }
+    BigUintTarget { limbs }
+}
+
+pub struct FactorTargets {
+    pub a: BigUintTarget,
+    pub b: BigUintTarget,
+    pub n: BigUintTarget,
+}
+
+pub fn factor_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a_limbs: usize,
+    b_limbs: usize,
+) -> FactorTargets {
+    let a = add_virtual_biguint_checked(builder, a_limbs);
+    let b = add_virtual_biguint_checked(builder, b_limbs);
+    let n = add_virtual_biguint_checked(builder, a_limbs + b_limbs);
+
+    let product = mul_biguint_exact(builder, &a, &b);
+    builder.connect_biguint(&product, &n);
+
+    // a > 1 and b > 1, with a * b = n this also means a < n and b < n
+    let one = builder.constant_biguint(&BigUint::one());
+    for x in [&a, &b] {
+        let at_most_one = builder.cmp_biguint(x, &one);
+        builder.assert_zero(at_most_one.target);
+    }
+
+    for limb in n.limbs.iter() {
+        builder.register_public_input(limb.0);
+    }
+
+    FactorTargets { a, b, n }
+}
+
+pub struct FactorCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub targets: FactorTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> FactorCircuit<F, C, D> {
+    // factors of up to a_bits and b_bits bits
+    pub fn new(a_bits: usize, b_bits: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = factor_circuit(&mut builder, num_limbs(a_bits), num_limbs(b_bits));
+        let data = builder.build::<C>();
+        FactorCircuit { targets, data }
+    }
+
+    pub fn prove(&self, a: &BigUint, b: &BigUint) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(*a > BigUint::one() && *b > BigUint::one(), "factors must be greater than 1");
+        ensure!(
+            a.to_u32_digits().len() <= self.targets.a.num_limbs()
+                && b.to_u32_digits().len() <= self.targets.b.num_limbs(),
+            "factor too large for the circuit"
+        );
+        let mut pw = PartialWitness::new();
+        pw.set_biguint_target(&self.targets.a, a);
+        pw.set_biguint_target(&self.targets.b, b);
+        pw.set_biguint_target(&self.targets.n, &(a * b));
+        self.data.prove(pw)
+    }
+}
+
+// native helpers
+
+pub fn biguint_from_limbs(limbs: &[u32]) -> BigUint {
+    BigUint::from_slice(limbs)
+}
+
+fn random_below<R: RngCore>(bound: &BigUint, rng: &mut R) -> BigUint {
+    let mut bytes = vec![0u8; (bound.bits() as usize).div_ceil(8) + 8];
+    rng.fill_bytes(&mut bytes);
+    BigUint::from_bytes_le(&bytes) % bound
+}
+
+fn small_primes() -> Vec<u32> {
+    (2..1000u32)
+        .filter(|&p| (2..p).take_while(|d| d * d <= p).all(|d| p % d != 0))
+        .collect()
+}
+
+// Miller-Rabin with random bases, after trial division by small primes
+pub fn is_probable_prime<R: RngCore>(n: &BigUint, rounds: usize, rng: &mut R) -> bool {
+    let two = BigUint::from(2u32);
+    if *n < two {
+        return false;
+    }
+    for p in small_primes() {
+        let p = BigUint::from(p);
+        if *n == p {
+            return true;
+        }
+        if (n % &p).is_zero() {
+            return false;
+        }
+    }
+
+    let n_minus_one = n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap();
+    let d = &n_minus_one >> s;
+    'witness: for _ in 0..rounds {
+        // base in [2, n - 2]
+        let base = random_below(&(n - 3u32), rng) + 2u32;
+        let mut x = base.modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// A random prime of exactly `bits` bits, with the top two bits set so the product
+/// of two such primes has exactly 2 * bits bits.
+pub fn random_prime<R: RngCore>(bits: u64, rng: &mut R) -> BigUint {
+    assert!(bits >= 16);
+    loop {
+        let mut candidate = random_below(&(BigUint::one() << bits), rng);
+        candidate.set_bit(bits - 1, true);
+        candidate.set_bit(bits - 2, true);
+        candidate.set_bit(0, true);
+        if is_probable_prime(&candidate, 40, rng) {
+            return candidate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn miller_rabin() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let one = BigUint::one();
+        let primes = [
+            BigUint::from(2u32),
+            BigUint::from(997u32),
+            BigUint::from(1009u32),
+            (&one << 64) - (&one << 32) + 1u32,
+            (&one << 127) - 1u32,
+        ];
+        for p in primes {
+            assert!(is_probable_prime(&p, 20, &mut rng), "{} is prime", p);
+        }
+        // a Carmichael number with no factor below 1000, a Fermat number and a product of two Mersenne primes
+        let composites = [
+            BigUint::from(0u32),
+            BigUint::from(1u32),
+            BigUint::from(1009u32 * 1013),
+            BigUint::from(1171u64 * 2341 * 3511),
+            (&one << 128) + 1u32,
+            ((&one << 61) - 1u32) * ((&one << 89) - 1u32),
+        ];
+        for n in composites {
+            assert!(!is_probable_prime(&n, 20, &mut rng), "{} is composite", n);
+        }
+
+        let p = random_prime(64, &mut rng);
+        assert_eq!(p.bits(), 64);
+        assert!(p.bit(62));
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        // 17 limbs each, so some columns of the product have more than 16 addends
+        let circuit = FactorCircuit::<F, C, D>::new(544, 544, ProofMode::Standard);
+        let a = (BigUint::one() << 544) - 1u32;
+        let b = (BigUint::one() << 543) + 3u32;
+        let proof = circuit.prove(&a, &b)?;
+        let mut expected = (&a * &b).to_u32_digits();
+        expected.resize(34, 0);
+        assert_eq!(
+            proof.public_inputs,
+            expected
+                .into_iter()
+                .map(F::from_canonical_u32)
+                .collect::<Vec<F>>()
+        );
+        circuit.data.verify(proof)?;
+
+        assert!(circuit.prove(&BigUint::one(), &b).is_err());
+        assert!(circuit.prove(&(BigUint::one() << 544), &b).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,491 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_u32::{
+    gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target},
+    witness::WitnessU32,
+};
+
+use crate::bits::{bits_to_u32, u32_to_bits, xor};
+use crate::zk::ProofMode;
+
+// Keccak-256 as used by Ethereum (padding 0x01, not the 0x06 of SHA3-256).
+// In the circuit a 64-bit lane is 64 little-endian BoolTargets, so rotations
+// are free rewiring and XOR / AND-NOT cost a couple of arithmetic operations per bit.
+// 32-byte values (digests, siblings, roots) are 8 little-endian u32 limbs,
+// limb i holding bytes 4i..4i+4, the order in which Keccak reads its lanes.
+
+pub const DIGEST_LIMBS: usize = 8;
+const RATE_BYTES: usize = 136;
+const NUM_ROUNDS: usize = 24;
+
+const ROUND_CONSTANTS: [u64; NUM_ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// rho offsets, indexed [x][y]
+const ROTATIONS: [[usize; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+pub type DigestTarget = [U32Target; DIGEST_LIMBS];
+
+// native helpers
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    keccak_hash::keccak_256(data, &mut out);
+    out
+}
+
+pub fn digest_to_limbs(digest: &[u8; 32]) -> [u32; DIGEST_LIMBS] {
+    core::array::from_fn(|i| u32::from_le_bytes(digest[4 * i..4 * i + 4].try_into().unwrap()))
+}
+
+pub fn limbs_to_digest(limbs: &[u32]) -> [u8; 32] {
+    let bytes = limbs
+        .iter()
+        .flat_map(|limb| limb.to_le_bytes())
+        .collect::<Vec<u8>>();
+    bytes.try_into().unwrap()
+}
+
+// Binary Keccak Merkle tree as built outside plonky2: leaf = keccak256(leaf bytes),
+// parent = keccak256(left || right), children ordered by position.
+pub struct KeccakMerkleTree {
+    pub leaves: Vec<Vec<u8>>,
+    // layers[0] are the leaf hashes, the last layer is the root
+    pub layers: Vec<Vec<[u8; 32]>>,
+}
+
+impl KeccakMerkleTree {
+    pub fn new(leaves: Vec<Vec<u8>>) -> Self {
+        assert!(leaves.len().is_power_of_two(), "number of leaves must be a power of two");
+        let mut layers = vec![leaves.iter().map(|leaf| keccak256(leaf)).collect::<Vec<_>>()];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| keccak256(&[pair[0], pair[1]].concat()))
+                .collect();
+            layers.push(next);
+        }
+        KeccakMerkleTree { leaves, layers }
+    }
+
+    pub fn height(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.layers.last().unwrap()[0]
+    }
+
+    // siblings from the bottom layer up
+    pub fn prove(&self, leaf_index: usize) -> Vec<[u8; 32]> {
+        (0..self.height())
+            .map(|layer| self.layers[layer][(leaf_index >> layer) ^ 1])
+            .collect()
+    }
+}
+
+pub fn verify_keccak_merkle_proof(
+    leaf: &[u8],
+    leaf_index: usize,
+    root: [u8; 32],
+    siblings: &[[u8; 32]],
+) -> Result<()> {
+    let mut current = keccak256(leaf);
+    for (layer, sibling) in siblings.iter().enumerate() {
+        current = if (leaf_index >> layer) & 1 == 1 {
+            keccak256(&[*sibling, current].concat())
+        } else {
+            keccak256(&[current, *sibling].concat())
+        };
+    }
+    ensure!(current == root, "invalid Keccak Merkle proof");
+    Ok(())
+}
+
+// a ^ (!b & c)
+fn chi<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: BoolTarget,
+    b: BoolTarget,
+    c: BoolTarget,
+) -> BoolTarget {
+    // c - bc
+    let and_not = builder.arithmetic(F::NEG_ONE, F::ONE, b.target, c.target, c.target);
+    xor(builder, a, BoolTarget::new_unsafe(and_not))
+}
+
+fn rotl(lane: &[BoolTarget], n: usize) -> Vec<BoolTarget> {
+    (0..64).map(|i| lane[(i + 64 - n) % 64]).collect()
+}
+
+// Keccak-f[1600] on 25 lanes, lane x + 5y at state[x + 5 * y]
+fn keccak_f<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    mut state: Vec<Vec<BoolTarget>>,
+) -> Vec<Vec<BoolTarget>> {
+    for round_constant in ROUND_CONSTANTS {
+        // theta
+        let parities = (0..5)
+            .map(|x| {
+                (0..64)
+                    .map(|i| {
+                        let mut parity = state[x][i];
+                        for y in 1..5 {
+                            parity = xor(builder, parity, state[x + 5 * y][i]);
+                        }
+                        parity
+                    })
+                    .collect::<Vec<BoolTarget>>()
+            })
+            .collect::<Vec<_>>();
+        for x in 0..5 {
+            let rotated = rotl(&parities[(x + 1) % 5], 1);
+            let d = (0..64)
+                .map(|i| xor(builder, parities[(x + 4) % 5][i], rotated[i]))
+                .collect::<Vec<BoolTarget>>();
+            for y in 0..5 {
+                for i in 0..64 {
+                    state[x + 5 * y][i] = xor(builder, state[x + 5 * y][i], d[i]);
+                }
+            }
+        }
+
+        // rho and pi
+        let mut b = vec![Vec::new(); 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = rotl(&state[x + 5 * y], ROTATIONS[x][y]);
+            }
+        }
+
+        // chi
+        for x in 0..5 {
+            for y in 0..5 {
+                state[x + 5 * y] = (0..64)
+                    .map(|i| {
+                        chi(
+                            builder,
+                            b[x + 5 * y][i],
+                            b[(x + 1) % 5 + 5 * y][i],
+                            b[(x + 2) % 5 + 5 * y][i],
+                        )
+                    })
+                    .collect();
+            }
+        }
+
+        // iota
+        for (i, bit) in state[0].iter_mut().enumerate() {
+            if (round_constant >> i) & 1 == 1 {
+                *bit = builder.not(*bit);
+            }
+        }
+    }
+    state
+}
+
+/// Keccak-256 of a message given as little-endian bits, returns the 256 digest bits.
+pub fn keccak256_bits_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    message: &[BoolTarget],
+) -> Vec<BoolTarget> {
+    assert_eq!(message.len() % 8, 0, "message must be whole bytes");
+    let rate = RATE_BYTES * 8;
+    let num_blocks = message.len() / rate + 1;
+
+    // pad10*1 with the Keccak domain byte 0x01
+    let mut padded = message.to_vec();
+    padded.push(builder._true());
+    padded.resize(num_blocks * rate - 1, builder._false());
+    padded.push(builder._true());
+
+    let mut state = vec![vec![builder._false(); 64]; 25];
+    for block in padded.chunks(rate) {
+        for (i, &bit) in block.iter().enumerate() {
+            state[i / 64][i % 64] = xor(builder, state[i / 64][i % 64], bit);
+        }
+        state = keccak_f(builder, state);
+    }
+    state[..4].concat()
+}
+
+fn bits_to_limbs<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    bits: &[BoolTarget],
+) -> DigestTarget {
+    core::array::from_fn(|i| bits_to_u32(builder, &bits[32 * i..32 * (i + 1)]))
+}
+
+// also range checks every limb to 32 bits
+fn limbs_to_bits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    limbs: &DigestTarget,
+) -> Vec<BoolTarget> {
+    limbs
+        .iter()
+        .flat_map(|&limb| u32_to_bits(builder, limb))
+        .collect()
+}
+
+/// Keccak-256 of a byte string, every byte is range checked.
+pub fn keccak256_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    bytes: &[Target],
+) -> DigestTarget {
+    let bits = bytes
+        .iter()
+        .flat_map(|&byte| builder.split_le(byte, 8))
+        .collect::<Vec<BoolTarget>>();
+    let digest = keccak256_bits_circuit(builder, &bits);
+    bits_to_limbs(builder, &digest)
+}
+
+pub fn add_virtual_digest<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> DigestTarget {
+    builder
+        .add_virtual_u32_targets(DIGEST_LIMBS)
+        .try_into()
+        .unwrap()
+}
+
+pub fn set_digest_target<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    target: &DigestTarget,
+    digest: &[u8; 32],
+) {
+    for (&limb, value) in target.iter().zip(digest_to_limbs(digest)) {
+        pw.set_u32_target(limb, value);
+    }
+}
+
+// Inclusion of a public leaf of `leaf_len` bytes in a Keccak Merkle tree with a public root.
+// Public inputs are laid out as follows:
+// 0..8 is the root as u32 limbs, then the leaf bytes.
+pub struct KeccakInclusionTargets {
+    pub leaf: Vec<Target>,
+    pub leaf_index: Target,
+    pub siblings: Vec<DigestTarget>,
+    pub root: DigestTarget,
+}
+
+pub fn keccak_inclusion_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf_len: usize,
+    height: usize,
+) -> KeccakInclusionTargets {
+    let leaf = builder.add_virtual_targets(leaf_len);
+    let leaf_index = builder.add_virtual_target();
+    let index_bits = builder.split_le(leaf_index, height);
+    let siblings = (0..height)
+        .map(|_| add_virtual_digest(builder))
+        .collect::<Vec<DigestTarget>>();
+    let root = add_virtual_digest(builder);
+
+    let mut current = keccak256_circuit(builder, &leaf);
+    for (&bit, sibling) in index_bits.iter().zip(siblings.iter()) {
+        // bit 1 means the current node is a right child
+        let (left, right): (Vec<U32Target>, Vec<U32Target>) = current
+            .iter()
+            .zip(sibling.iter())
+            .map(|(c, s)| {
+                let left = builder.select(bit, s.0, c.0);
+                let right = builder.select(bit, c.0, s.0);
+                (U32Target(left), U32Target(right))
+            })
+            .unzip();
+        let message = [
+            limbs_to_bits(builder, &left.try_into().unwrap()),
+            limbs_to_bits(builder, &right.try_into().unwrap()),
+        ]
+        .concat();
+        let digest = keccak256_bits_circuit(builder, &message);
+        current = bits_to_limbs(builder, &digest);
+    }
+    for (&computed, &expected) in current.iter().zip(root.iter()) {
+        builder.connect_u32(computed, expected);
+    }
+
+    builder.register_public_inputs(&root.map(|limb| limb.0));
+    builder.register_public_inputs(&leaf);
+
+    KeccakInclusionTargets {
+        leaf,
+        leaf_index,
+        siblings,
+        root,
+    }
+}
+
+pub struct KeccakInclusionCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub targets: KeccakInclusionTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    KeccakInclusionCircuit<F, C, D>
+{
+    pub fn new(leaf_len: usize, height: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = keccak_inclusion_circuit(&mut builder, leaf_len, height);
+        let data = builder.build::<C>();
+        KeccakInclusionCircuit { targets, data }
+    }
+
+    pub fn prove(
+        &self,
+        tree: &KeccakMerkleTree,
+        leaf_index: usize,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(leaf_index < tree.leaves.len(), "leaf index out of range");
+        let leaf = &tree.leaves[leaf_index];
+        ensure!(leaf.len() == self.targets.leaf.len(), "wrong leaf length");
+        ensure!(tree.height() == self.targets.siblings.len(), "wrong tree height");
+
+        let mut pw = PartialWitness::new();
+        for (&target, &byte) in self.targets.leaf.iter().zip(leaf.iter()) {
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        pw.set_target(self.targets.leaf_index, F::from_canonical_usize(leaf_index));
+        for (target, sibling) in self.targets.siblings.iter().zip(tree.prove(leaf_index)) {
+            set_digest_target(&mut pw, target, &sibling);
+        }
+        set_digest_target(&mut pw, &self.targets.root, &tree.root());
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const EMPTY: &str = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+    const ABC: &str = "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45";
+
+    fn from_hex(hex: &str) -> [u8; 32] {
+        core::array::from_fn(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+    }
+
+    // proves keccak256(message) == digest with the message as a witness
+    fn prove_digest(message: &[u8], digest: &[u8; 32]) -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let bytes = builder.add_virtual_targets(message.len());
+        let computed = keccak256_circuit(&mut builder, &bytes);
+        let expected = add_virtual_digest(&mut builder);
+        for (&c, &e) in computed.iter().zip(expected.iter()) {
+            builder.connect_u32(c, e);
+        }
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        for (&target, &byte) in bytes.iter().zip(message.iter()) {
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        set_digest_target(&mut pw, &expected, digest);
+        data.verify(data.prove(pw)?)
+    }
+
+    #[test]
+    fn native_known_answers() {
+        assert_eq!(keccak256(b""), from_hex(EMPTY));
+        assert_eq!(keccak256(b"abc"), from_hex(ABC));
+    }
+
+    #[test]
+    fn circuit_known_answers() -> Result<()> {
+        prove_digest(b"abc", &from_hex(ABC))?;
+        // a full rate block, so the padding takes a second one
+        let message = (0..RATE_BYTES as u8).collect::<Vec<u8>>();
+        prove_digest(&message, &keccak256(&message))
+    }
+
+    #[test]
+    fn limbs_round_trip() {
+        let digest = from_hex(ABC);
+        assert_eq!(limbs_to_digest(&digest_to_limbs(&digest)), digest);
+        assert_eq!(digest_to_limbs(&digest)[0], 0x7a65034e);
+    }
+
+    #[test]
+    fn merkle_proofs() {
+        let leaves = (0..8u8).map(|i| vec![i; 3]).collect::<Vec<_>>();
+        let tree = KeccakMerkleTree::new(leaves.clone());
+        assert_eq!(
+            tree.root(),
+            keccak256(&[tree.layers[2][0], tree.layers[2][1]].concat())
+        );
+        for (i, leaf) in leaves.iter().enumerate() {
+            let siblings = tree.prove(i);
+            verify_keccak_merkle_proof(leaf, i, tree.root(), &siblings).unwrap();
+            assert!(verify_keccak_merkle_proof(leaf, i ^ 1, tree.root(), &siblings).is_err());
+        }
+    }
+
+    #[test]
+    fn inclusion_rejects_bad_index() {
+        let tree = KeccakMerkleTree::new(vec![vec![1; 3], vec![2; 3]]);
+        let circuit = KeccakInclusionCircuit::<F, C, D>::new(3, 1, ProofMode::Standard);
+        assert!(circuit.prove(&tree, 2).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+// Circuits and gadgets shared between the examples in examples/.
+
+pub mod aggregate;
+pub mod batch_ecdsa;
+pub mod batch_update;
+pub mod bits;
+pub mod compress;
+pub mod ecdsa;
+pub mod ecgfp5;
+pub mod factor;
+pub mod keccak;
+pub mod merkle;
+pub mod merkle_subset;
+pub mod merkle_update;
+pub mod multiproof;
+pub mod pow5;
+pub mod recursion;
+pub mod rsa;
+pub mod schnorr;
+pub mod semaphore;
+pub mod sha256;
+pub mod shrink;
+pub mod smt;
+pub mod statement;
+pub mod tornado;
+pub mod whitelist;
+pub mod winternitz;
+pub mod xmss;
+pub mod zk;
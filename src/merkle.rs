@@ -0,0 +1,154 @@
+use std::iter::repeat;
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, MerkleCapTarget, RichField, NUM_HASH_OUT_ELTS},
+        hashing::PlonkyPermutation,
+        merkle_proofs::MerkleProof,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        config::{AlgebraicHasher, Hasher},
+    },
+};
+
+// In-circuit Merkle path with the same layout as `MerkleTree`: a leaf of 4 field
+// elements is its own digest and a parent is two_to_one(left, right).
+// The circuits are generic over any `AlgebraicHasher` H and match `MerkleTree<F, H>`.
+// Native helpers like `zero_hash` only need `Hasher`, so they also work with
+// non-algebraic hashes such as `KeccakHash`.
+
+// digest of an empty leaf
+pub fn zero_hash<F: RichField, H: Hasher<F>>() -> H::Hash {
+    let input = [F::ZERO, F::ZERO, F::ZERO, F::ZERO];
+    H::hash_no_pad(&input)
+}
+
+pub struct MerkleProofTargets {
+    pub leaf_index: Target,
+    pub index_bits: Vec<BoolTarget>,
+    pub siblings: Vec<HashOutTarget>,
+}
+
+pub fn add_virtual_merkle_proof<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> MerkleProofTargets {
+    add_virtual_merkle_proof_to_cap(builder, height, 0)
+}
+
+// path for a tree committed to by a cap, with siblings only up to the cap layer
+pub fn add_virtual_merkle_proof_to_cap<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+    cap_height: usize,
+) -> MerkleProofTargets {
+    let leaf_index = builder.add_virtual_target();
+    // also checks leaf_index < 2^height
+    let index_bits = builder.split_le(leaf_index, height);
+    let siblings = builder.add_virtual_hashes(height - cap_height);
+    MerkleProofTargets {
+        leaf_index,
+        index_bits,
+        siblings,
+    }
+}
+
+// returns x if b is true, y otherwise
+pub fn select_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    b: BoolTarget,
+    x: HashOutTarget,
+    y: HashOutTarget,
+) -> HashOutTarget {
+    let elements = (0..4)
+        .map(|i| builder.select(b, x.elements[i], y.elements[i]))
+        .collect::<Vec<Target>>();
+    HashOutTarget::from_vec(elements)
+}
+
+// hash of two digests, equal to H::two_to_one natively: one permutation of
+// (left, right, 0, ...), keeping the first 4 elements
+pub fn two_to_one_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    left: HashOutTarget,
+    right: HashOutTarget,
+) -> HashOutTarget {
+    let zero = builder.zero();
+    let mut state = H::AlgebraicPermutation::new(repeat(zero));
+    state.set_from_slice(&left.elements, 0);
+    state.set_from_slice(&right.elements, NUM_HASH_OUT_ELTS);
+    let state = builder.permute::<H>(state);
+    HashOutTarget::from_vec(state.squeeze()[..NUM_HASH_OUT_ELTS].to_vec())
+}
+
+/// Computes the root reached from `leaf` along the path, walking up from the bottom layer.
+pub fn merkle_root_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf: HashOutTarget,
+    proof: &MerkleProofTargets,
+) -> HashOutTarget {
+    merkle_root_from_bits::<H, F, D>(builder, leaf, &proof.index_bits, &proof.siblings)
+}
+
+// same as `merkle_root_circuit`, for paths that do not come from a single index target
+pub fn merkle_root_from_bits<
+    H: AlgebraicHasher<F>,
+    F: RichField + Extendable<D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf: HashOutTarget,
+    index_bits: &[BoolTarget],
+    siblings: &[HashOutTarget],
+) -> HashOutTarget {
+    let mut current = leaf;
+    for (&bit, &sibling) in index_bits.iter().zip(siblings.iter()) {
+        // bit 1 means the current node is a right child
+        let left = select_hash(builder, bit, sibling, current);
+        let right = select_hash(builder, bit, current, sibling);
+        current = two_to_one_circuit::<H, F, D>(builder, left, right);
+    }
+    current
+}
+
+/// Checks that `leaf` is in the tree with the given cap, like `MerkleTree` FRI caps.
+/// The low index bits walk the path up to the cap layer, the high bits select the cap entry.
+pub fn verify_merkle_proof_to_cap_circuit<
+    H: AlgebraicHasher<F>,
+    F: RichField + Extendable<D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf: HashOutTarget,
+    proof: &MerkleProofTargets,
+    cap: &MerkleCapTarget,
+) {
+    let path_len = proof.siblings.len();
+    let digest = merkle_root_from_bits::<H, F, D>(
+        builder,
+        leaf,
+        &proof.index_bits[..path_len],
+        &proof.siblings,
+    );
+    let cap_index = builder.le_sum(proof.index_bits[path_len..].iter());
+    let cap_entry = builder.random_access_hash(cap_index, cap.0.clone());
+    builder.connect_hashes(digest, cap_entry);
+}
+
+pub fn set_merkle_proof_witness<F: RichField, H: Hasher<F, Hash = HashOut<F>>>(
+    pw: &mut PartialWitness<F>,
+    targets: &MerkleProofTargets,
+    leaf_index: usize,
+    proof: &MerkleProof<F, H>,
+) {
+    pw.set_target(targets.leaf_index, F::from_canonical_usize(leaf_index));
+    for (&target, &sibling) in targets.siblings.iter().zip(proof.siblings.iter()) {
+        pw.set_hash_target(target, sibling);
+    }
+}
@@ -0,0 +1,94 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::{HashOutTarget, RichField},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::merkle::zero_hash;
+use crate::zk::ProofMode;
+
+// One leaf of a Merkle subset check: every element of the subset leaf is the one of the
+// original leaf or of the empty leaf zero_hash(). Leaves are 4 elements, their own digest.
+// Public inputs are the original then the subset leaf, so merging the leaves with
+// `TwoToOnePolicy<H>` gives the roots of both trees, and the subset tree is the original
+// one with some leaves emptied.
+
+pub struct SubsetTargets {
+    pub original: HashOutTarget,
+    pub subset: HashOutTarget,
+}
+
+pub fn subset_leaf_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> SubsetTargets {
+    let hash_of_zero = zero_hash::<F, H>();
+    let original = builder.add_virtual_hash();
+    let subset = builder.add_virtual_hash();
+    for i in 0..4 {
+        // (original - subset) * (zero - subset) = 0
+        let diff = builder.sub(original.elements[i], subset.elements[i]);
+        let neg = builder.neg(subset.elements[i]);
+        let to_zero = builder.add_const(neg, hash_of_zero.elements[i]);
+        let t = builder.mul(diff, to_zero);
+        builder.assert_zero(t);
+    }
+    builder.register_public_inputs(&original.elements);
+    builder.register_public_inputs(&subset.elements);
+    SubsetTargets { original, subset }
+}
+
+pub struct SubsetCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub targets: SubsetTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > SubsetCircuit<F, C, H, D>
+{
+    pub fn new(proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = subset_leaf_circuit::<H, F, D>(&mut builder);
+        let data = builder.build::<C>();
+        SubsetCircuit {
+            targets,
+            data,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn prove(&self, original: &[F], subset: &[F]) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            original.len() == 4 && subset.len() == 4,
+            "leaves must be 4 elements"
+        );
+        let hash_of_zero = zero_hash::<F, H>();
+        ensure!(
+            (0..4).all(|i| subset[i] == original[i] || subset[i] == hash_of_zero.elements[i]),
+            "subset leaf is neither the original nor the empty one"
+        );
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&self.targets.original.elements, original);
+        pw.set_target_arr(&self.targets.subset.elements, subset);
+        self.data.prove(pw)
+    }
+}
@@ -0,0 +1,207 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_tree::MerkleTree,
+    },
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::merkle::{
+    add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness, MerkleProofTargets,
+};
+use crate::zk::ProofMode;
+
+// State transition of a Merkle tree: replacing one leaf takes old_root to new_root.
+// Both roots are computed from the same sibling path, so nothing else in the tree changes.
+//
+// Public inputs are laid out as follows:
+// 0..4 is the old root, 4..8 the new root.
+
+pub struct MerkleUpdateTargets {
+    pub old_root: HashOutTarget,
+    pub new_root: HashOutTarget,
+    pub old_leaf: HashOutTarget,
+    pub new_leaf: HashOutTarget,
+    pub merkle_proof: MerkleProofTargets,
+}
+
+pub fn merkle_update_circuit<
+    H: AlgebraicHasher<F>,
+    F: RichField + Extendable<D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> MerkleUpdateTargets {
+    let old_root = builder.add_virtual_hash();
+    let old_leaf = builder.add_virtual_hash();
+    let new_leaf = builder.add_virtual_hash();
+    let merkle_proof = add_virtual_merkle_proof(builder, height);
+
+    let computed_old_root = merkle_root_circuit::<H, F, D>(builder, old_leaf, &merkle_proof);
+    builder.connect_hashes(computed_old_root, old_root);
+    let new_root = merkle_root_circuit::<H, F, D>(builder, new_leaf, &merkle_proof);
+
+    builder.register_public_inputs(&old_root.elements);
+    builder.register_public_inputs(&new_root.elements);
+
+    MerkleUpdateTargets {
+        old_root,
+        new_root,
+        old_leaf,
+        new_leaf,
+        merkle_proof,
+    }
+}
+
+// native helper, returns the tree with leaf `leaf_index` replaced
+pub fn update_leaf<F: RichField, H: AlgebraicHasher<F>>(
+    tree: &MerkleTree<F, H>,
+    leaf_index: usize,
+    new_leaf: HashOut<F>,
+) -> MerkleTree<F, H> {
+    let mut leaves = tree.leaves.clone();
+    leaves[leaf_index] = new_leaf.elements.to_vec();
+    MerkleTree::new(leaves, 0)
+}
+
+pub struct MerkleUpdateCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub targets: MerkleUpdateTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > MerkleUpdateCircuit<F, C, H, D>
+{
+    pub fn new(height: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = merkle_update_circuit::<H, F, D>(&mut builder, height);
+        let data = builder.build::<C>();
+        MerkleUpdateCircuit {
+            targets,
+            data,
+            _hasher: PhantomData,
+        }
+    }
+
+    // proves the update and returns it together with the updated tree
+    pub fn prove(
+        &self,
+        tree: &MerkleTree<F, H>,
+        leaf_index: usize,
+        new_leaf: HashOut<F>,
+    ) -> Result<(ProofWithPublicInputs<F, C, D>, MerkleTree<F, H>)> {
+        ensure!(tree.cap.0.len() == 1, "the tree must have a single root");
+        ensure!(leaf_index < tree.leaves.len(), "leaf index out of range");
+        ensure!(tree.leaves[leaf_index].len() == 4, "leaves must be 4 field elements");
+
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(self.targets.old_root, tree.cap.0[0]);
+        pw.set_hash_target(
+            self.targets.old_leaf,
+            HashOut::from_vec(tree.leaves[leaf_index].clone()),
+        );
+        pw.set_hash_target(self.targets.new_leaf, new_leaf);
+        set_merkle_proof_witness(
+            &mut pw,
+            &self.targets.merkle_proof,
+            leaf_index,
+            &tree.prove(leaf_index),
+        );
+        let proof = self.data.prove(pw)?;
+        Ok((proof, update_leaf(tree, leaf_index, new_leaf)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        hash::poseidon::PoseidonHash,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    const HEIGHT: usize = 3;
+
+    fn tree() -> MerkleTree<F, H> {
+        let leaves = (0..1 << HEIGHT)
+            .map(|i| vec![F::from_canonical_u64(i), F::ZERO, F::ZERO, F::ZERO])
+            .collect::<Vec<Vec<F>>>();
+        MerkleTree::new(leaves, 0)
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let tree = tree();
+        let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+        let new_leaf = HashOut::from_partial(&[F::TWO, F::ONE]);
+
+        let (proof, updated) = circuit.prove(&tree, 6, new_leaf)?;
+        assert_eq!(updated.leaves[6], new_leaf.elements);
+        assert_eq!(updated.leaves[5], tree.leaves[5]);
+        assert_eq!(
+            proof.public_inputs,
+            [tree.cap.0[0].elements, updated.cap.0[0].elements].concat()
+        );
+        circuit.data.verify(proof)?;
+
+        assert!(circuit.prove(&tree, 1 << HEIGHT, new_leaf).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_stale_root() -> Result<()> {
+        let tree = tree();
+        let circuit = MerkleUpdateCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+        let (_, updated) = circuit.prove(&tree, 6, HashOut::from_partial(&[F::TWO]))?;
+
+        // an update made on the tree before the first one starts from its old root,
+        // not from the current state
+        let (stale, _) = circuit.prove(&tree, 2, HashOut::from_partial(&[F::ONE]))?;
+        assert_ne!(stale.public_inputs[0..4], updated.cap.0[0].elements);
+
+        // and claiming the current root with the old path does not prove
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut pw = PartialWitness::new();
+            pw.set_hash_target(circuit.targets.old_root, updated.cap.0[0]);
+            pw.set_hash_target(
+                circuit.targets.old_leaf,
+                HashOut::from_vec(tree.leaves[2].clone()),
+            );
+            pw.set_hash_target(circuit.targets.new_leaf, HashOut::from_partial(&[F::ONE]));
+            set_merkle_proof_witness(&mut pw, &circuit.targets.merkle_proof, 2, &tree.prove(2));
+            circuit.data.prove(pw)
+        }));
+        assert!(!matches!(result, Ok(Ok(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_tree::MerkleTree,
+    },
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::merkle::two_to_one_circuit;
+use crate::zk::ProofMode;
+
+// Merkle multiproof: k leaves proven against one root with every sibling sent once.
+// Nodes on the paths of other proven leaves are not sent at all, and shared
+// ancestors are hashed once, so the circuit needs fewer hashes than k paths.
+//
+// Both the prover and the circuit walk the tree layer by layer, left to right,
+// and take the next sibling from the proof whenever it is not already known.
+// The walk only depends on the leaf indices, so the circuit is a fixed-index one: the
+// indices are constants of the circuit, and another set of indices needs another circuit
+// and verifier key. They are also public inputs, so a verifier sees which leaves were
+// proven without knowing the circuit.
+// Leaves are taken as `MerkleTree` digests them, with `hash_or_noop`.
+
+pub struct MerkleMultiproof<F: RichField> {
+    pub siblings: Vec<HashOut<F>>,
+}
+
+// One hash of the walk: the parent's index and where both children come from.
+struct HashStep {
+    parent: usize,
+    left: Node,
+    right: Node,
+}
+
+enum Node {
+    // index of a node that is already known on this layer
+    Known(usize),
+    // position in the sibling list of the proof
+    Sibling(usize),
+}
+
+// The hashes needed for the given leaves, layer by layer, and the (layer, index)
+// of every sibling in the order they appear in the proof.
+type Schedule = (Vec<Vec<HashStep>>, Vec<(usize, usize)>);
+
+fn schedule(height: usize, indices: &[usize]) -> Result<Schedule> {
+    ensure!(!indices.is_empty(), "no leaf indices");
+    ensure!(
+        indices.windows(2).all(|pair| pair[0] < pair[1]),
+        "leaf indices must be sorted and distinct"
+    );
+    ensure!(
+        indices.iter().all(|&index| index >> height == 0),
+        "leaf index out of range"
+    );
+    let mut known = indices.to_vec();
+
+    let mut layers = Vec::new();
+    let mut siblings = Vec::new();
+    for layer in 0..height {
+        let mut steps = Vec::new();
+        for (i, &index) in known.iter().enumerate() {
+            if index & 1 == 1 && i > 0 && known[i - 1] == index - 1 {
+                // already hashed together with its left sibling
+                continue;
+            }
+            let other = if known.get(i + 1) == Some(&(index ^ 1)) {
+                Node::Known(index ^ 1)
+            } else {
+                siblings.push((layer, index ^ 1));
+                Node::Sibling(siblings.len() - 1)
+            };
+            let (left, right) = if index & 1 == 0 {
+                (Node::Known(index), other)
+            } else {
+                (other, Node::Known(index))
+            };
+            steps.push(HashStep {
+                parent: index >> 1,
+                left,
+                right,
+            });
+        }
+        known = steps.iter().map(|step| step.parent).collect();
+        layers.push(steps);
+    }
+    Ok((layers, siblings))
+}
+
+// Computes the root from the leaves and the siblings, using `hash` as two_to_one.
+fn evaluate<T: Copy>(
+    height: usize,
+    indices: &[usize],
+    leaves: &[T],
+    siblings: &[T],
+    mut hash: impl FnMut(T, T) -> T,
+) -> Result<T> {
+    ensure!(
+        leaves.len() == indices.len(),
+        "{} leaves for {} indices",
+        leaves.len(),
+        indices.len()
+    );
+    let (layers, _) = schedule(height, indices)?;
+    let mut known = indices
+        .iter()
+        .copied()
+        .zip(leaves.iter().copied())
+        .collect::<BTreeMap<usize, T>>();
+    for steps in layers.iter() {
+        let mut next = BTreeMap::new();
+        for step in steps.iter() {
+            let resolve = |node: &Node| match node {
+                Node::Known(index) => known[index],
+                Node::Sibling(position) => siblings[*position],
+            };
+            let parent = hash(resolve(&step.left), resolve(&step.right));
+            next.insert(step.parent, parent);
+        }
+        known = next;
+    }
+    Ok(known[&0])
+}
+
+pub fn num_siblings(height: usize, indices: &[usize]) -> Result<usize> {
+    Ok(schedule(height, indices)?.1.len())
+}
+
+pub fn num_hashes(height: usize, indices: &[usize]) -> Result<usize> {
+    Ok(schedule(height, indices)?
+        .0
+        .iter()
+        .map(|steps| steps.len())
+        .sum())
+}
+
+// digest of a leaf, as in `MerkleTree`
+pub fn leaf_digest<F: RichField, H: AlgebraicHasher<F>>(leaf: &[F]) -> HashOut<F> {
+    H::hash_or_noop(leaf)
+}
+
+pub fn prove_multi<F: RichField, H: AlgebraicHasher<F>>(
+    tree: &MerkleTree<F, H>,
+    indices: &[usize],
+) -> Result<MerkleMultiproof<F>> {
+    ensure!(tree.cap.height() == 0, "the tree must have a single root");
+    let height = tree.leaves.len().trailing_zeros() as usize;
+    let (_, sibling_positions) = schedule(height, indices)?;
+    // digests of every layer
+    let mut layers = vec![tree
+        .leaves
+        .iter()
+        .map(|leaf| leaf_digest::<F, H>(leaf))
+        .collect::<Vec<HashOut<F>>>()];
+    for layer in 0..height {
+        let next = layers[layer]
+            .chunks(2)
+            .map(|pair| H::two_to_one(pair[0], pair[1]))
+            .collect();
+        layers.push(next);
+    }
+
+    let siblings = sibling_positions
+        .iter()
+        .map(|&(layer, index)| layers[layer][index])
+        .collect();
+    Ok(MerkleMultiproof { siblings })
+}
+
+pub fn verify_multi<F: RichField, H: AlgebraicHasher<F>>(
+    height: usize,
+    indices: &[usize],
+    leaves: &[HashOut<F>],
+    root: HashOut<F>,
+    proof: &MerkleMultiproof<F>,
+) -> Result<()> {
+    ensure!(
+        proof.siblings.len() == num_siblings(height, indices)?,
+        "wrong number of siblings"
+    );
+    let computed_root = evaluate(height, indices, leaves, &proof.siblings, H::two_to_one)?;
+    ensure!(computed_root == root, "Invalid Merkle multiproof.");
+    Ok(())
+}
+
+pub struct MultiproofTargets {
+    pub leaves: Vec<HashOutTarget>,
+    pub siblings: Vec<HashOutTarget>,
+    pub root: HashOutTarget,
+}
+
+/// Inclusion of the leaves at the constant `indices` in a tree of height `height`.
+/// Public inputs are the root, the k leaf digests, then the k leaf indices.
+pub fn fixed_index_multiproof_circuit<
+    H: AlgebraicHasher<F>,
+    F: RichField + Extendable<D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+    indices: &[usize],
+) -> Result<MultiproofTargets> {
+    let leaves = builder.add_virtual_hashes(indices.len());
+    let root = builder.add_virtual_hash();
+
+    let siblings = builder.add_virtual_hashes(num_siblings(height, indices)?);
+    let computed_root = evaluate(height, indices, &leaves, &siblings, |left, right| {
+        two_to_one_circuit::<H, F, D>(builder, left, right)
+    })?;
+    builder.connect_hashes(computed_root, root);
+
+    builder.register_public_inputs(&root.elements);
+    for leaf in leaves.iter() {
+        builder.register_public_inputs(&leaf.elements);
+    }
+    for &index in indices.iter() {
+        let index = builder.constant(F::from_canonical_usize(index));
+        builder.register_public_input(index);
+    }
+
+    Ok(MultiproofTargets {
+        leaves,
+        siblings,
+        root,
+    })
+}
+
+pub struct FixedIndexMultiproofCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub height: usize,
+    pub indices: Vec<usize>,
+    pub targets: MultiproofTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > FixedIndexMultiproofCircuit<F, C, H, D>
+{
+    pub fn new(height: usize, indices: &[usize], proof_mode: ProofMode) -> Result<Self> {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = fixed_index_multiproof_circuit::<H, F, D>(&mut builder, height, indices)?;
+        let data = builder.build::<C>();
+        Ok(FixedIndexMultiproofCircuit {
+            height,
+            indices: indices.to_vec(),
+            targets,
+            data,
+            _hasher: PhantomData,
+        })
+    }
+
+    pub fn prove(
+        &self,
+        tree: &MerkleTree<F, H>,
+        proof: &MerkleMultiproof<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(tree.cap.height() == 0, "the tree must have a single root");
+        ensure!(
+            tree.leaves.len() == 1 << self.height,
+            "tree is of another height than the circuit's"
+        );
+        ensure!(
+            proof.siblings.len() == self.targets.siblings.len(),
+            "multiproof is for other leaves"
+        );
+        let mut pw = PartialWitness::new();
+        for (&target, &index) in self.targets.leaves.iter().zip(self.indices.iter()) {
+            pw.set_hash_target(target, leaf_digest::<F, H>(&tree.leaves[index]));
+        }
+        for (&target, &sibling) in self.targets.siblings.iter().zip(proof.siblings.iter()) {
+            pw.set_hash_target(target, sibling);
+        }
+        pw.set_hash_target(self.targets.root, tree.cap.0[0]);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        hash::poseidon::PoseidonHash,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    const HEIGHT: usize = 5;
+
+    fn tree() -> MerkleTree<F, H> {
+        let leaves = (0..1 << HEIGHT)
+            .map(|i| vec![F::from_canonical_u64(i), F::ONE, F::TWO, F::ZERO, F::ZERO])
+            .collect::<Vec<Vec<F>>>();
+        MerkleTree::new(leaves, 0)
+    }
+
+    fn digests(tree: &MerkleTree<F, H>, indices: &[usize]) -> Vec<HashOut<F>> {
+        indices
+            .iter()
+            .map(|&i| leaf_digest::<F, H>(&tree.leaves[i]))
+            .collect()
+    }
+
+    #[test]
+    fn native_round_trips() -> Result<()> {
+        let tree = tree();
+        let root = tree.cap.0[0];
+        let all = (0..1 << HEIGHT).collect::<Vec<usize>>();
+        // (indices, siblings, hashes)
+        let cases: [(&[usize], usize, usize); 5] = [
+            // one path
+            (&[5], HEIGHT, HEIGHT),
+            // siblings of each other, their parent is known on the next layer
+            (&[6, 7], HEIGHT - 1, HEIGHT),
+            // in different halves, the paths only meet at the root
+            (&[0, 31], 2 * (HEIGHT - 1), 2 * HEIGHT - 1),
+            (&[1, 2, 13, 30], 10, 13),
+            // the whole tree: nothing to send, every node hashed once
+            (&all, 0, (1 << HEIGHT) - 1),
+        ];
+        for (indices, siblings, hashes) in cases {
+            let proof = prove_multi(&tree, indices)?;
+            assert_eq!(proof.siblings.len(), siblings, "siblings of {:?}", indices);
+            assert_eq!(
+                num_hashes(HEIGHT, indices)?,
+                hashes,
+                "hashes of {:?}",
+                indices
+            );
+            verify_multi::<F, H>(HEIGHT, indices, &digests(&tree, indices), root, &proof)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_input() -> Result<()> {
+        let tree = tree();
+        let root = tree.cap.0[0];
+        for indices in [&[][..], &[3, 2], &[2, 2], &[1 << HEIGHT]] {
+            assert!(
+                prove_multi(&tree, indices).is_err(),
+                "{:?} accepted",
+                indices
+            );
+        }
+
+        let indices = [2, 9];
+        let proof = prove_multi(&tree, &indices)?;
+        let leaves = digests(&tree, &indices);
+        assert!(verify_multi::<F, H>(HEIGHT, &[9, 2], &leaves, root, &proof).is_err());
+        assert!(verify_multi::<F, H>(HEIGHT, &indices, &leaves[..1], root, &proof).is_err());
+        // another leaf, another root
+        let swapped = [leaves[1], leaves[0]];
+        assert!(verify_multi::<F, H>(HEIGHT, &indices, &swapped, root, &proof).is_err());
+
+        let mut tampered = MerkleMultiproof {
+            siblings: proof.siblings.clone(),
+        };
+        tampered.siblings[1].elements[0] += F::ONE;
+        assert!(verify_multi::<F, H>(HEIGHT, &indices, &leaves, root, &tampered).is_err());
+
+        assert!(prove_multi(&MerkleTree::<F, H>::new(tree.leaves.clone(), 1), &indices).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let tree = tree();
+        let indices = [1, 2, 13, 30];
+        let circuit =
+            FixedIndexMultiproofCircuit::<F, C, H, D>::new(HEIGHT, &indices, ProofMode::Standard)?;
+        let proof = prove_multi(&tree, &indices)?;
+        let zk_proof = circuit.prove(&tree, &proof)?;
+        let mut expected = tree.cap.0[0].elements.to_vec();
+        expected.extend(digests(&tree, &indices).iter().flat_map(|d| d.elements));
+        expected.extend(indices.map(F::from_canonical_usize));
+        assert_eq!(zk_proof.public_inputs, expected);
+        circuit.data.verify(zk_proof)?;
+
+        assert!(FixedIndexMultiproofCircuit::<F, C, H, D>::new(
+            HEIGHT,
+            &[2, 1],
+            ProofMode::Standard
+        )
+        .is_err());
+
+        // a wrong sibling gives another root, which fails witness generation
+        let mut tampered = MerkleMultiproof {
+            siblings: proof.siblings.clone(),
+        };
+        tampered.siblings[0].elements[0] += F::ONE;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| circuit.prove(&tree, &tampered)));
+        assert!(
+            !matches!(result, Ok(Ok(_))),
+            "tampered multiproof was accepted"
+        );
+        Ok(())
+    }
+}
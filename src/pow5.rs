@@ -0,0 +1,56 @@
+use anyhow::Result;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder, circuit_data::CircuitData, config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::zk::ProofMode;
+
+// One step of the pow5 chain: "output = 5 * input". Both are public, input first, so
+// merging consecutive steps with `ChainPolicy { width: 1 }` proves x -> 5^n x.
+
+pub struct Pow5Targets {
+    pub input: Target,
+    pub output: Target,
+}
+
+pub fn pow5_step_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> Pow5Targets {
+    let input = builder.add_virtual_target();
+    let output = builder.mul_const(F::from_canonical_u64(5), input);
+    builder.register_public_input(input);
+    builder.register_public_input(output);
+    Pow5Targets { input, output }
+}
+
+pub struct Pow5Circuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub targets: Pow5Targets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    Pow5Circuit<F, C, D>
+{
+    pub fn new(proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = pow5_step_circuit(&mut builder);
+        let data = builder.build::<C>();
+        Pow5Circuit { targets, data }
+    }
+
+    pub fn prove(&self, input: F) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let mut pw = PartialWitness::new();
+        pw.set_target(self.targets.input, input);
+        self.data.prove(pw)
+    }
+}
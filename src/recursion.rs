@@ -0,0 +1,433 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::{HashOutTarget, RichField},
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitData, CommonCircuitData, VerifierCircuitData, VerifierOnlyCircuitData,
+        },
+        config::{AlgebraicHasher, GenericConfig},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+};
+
+use crate::merkle::two_to_one_circuit;
+use crate::zk::ProofMode;
+
+// A proof together with the circuit data needed to verify it recursively.
+// A proof of depth 0 is a leaf. Any other, made by merging or aggregating proofs, ends
+// with two public inputs: its depth and the number of leaves below it, both constrained
+// from its children, so that e.g. the root of a pow5 chain states how many steps it covers.
+#[derive(Clone)]
+pub struct ProofTuple<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub proof: ProofWithPublicInputs<F, C, D>,
+    pub vd: VerifierOnlyCircuitData<C, D>,
+    pub cd: CommonCircuitData<F, D>,
+    pub depth: u32,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> ProofTuple<F, C, D> {
+    // a proof made with `data`, as a leaf of an aggregation
+    pub fn leaf(proof: ProofWithPublicInputs<F, C, D>, data: &CircuitData<F, C, D>) -> Self {
+        ProofTuple {
+            proof,
+            vd: data.verifier_only.clone(),
+            cd: data.common.clone(),
+            depth: 0,
+        }
+    }
+
+    // the number of leaves the proof covers
+    pub fn leaves(&self) -> u64 {
+        match self.depth {
+            0 => 1,
+            _ => self.proof.public_inputs[self.proof.public_inputs.len() - 1].to_canonical_u64(),
+        }
+    }
+
+    // the public inputs without the depth and leaf count
+    pub fn statement(&self) -> &[F] {
+        let public_inputs = &self.proof.public_inputs;
+        match self.depth {
+            0 => public_inputs,
+            _ => &public_inputs[..public_inputs.len() - TREE_INFO_LEN],
+        }
+    }
+
+    // whether the proof hides its witness, as recorded in its circuit's common data
+    pub fn mode(&self) -> ProofMode {
+        ProofMode::of(&self.cd)
+    }
+
+    // the data needed to verify the proof outside a circuit
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        VerifierCircuitData {
+            verifier_only: self.vd.clone(),
+            common: self.cd.clone(),
+        }
+    }
+}
+
+// the last public inputs of a merged proof: its depth, then its number of leaves
+pub const TREE_INFO_LEN: usize = 2;
+
+/// The public inputs of `pt`, a proof of depth `depth` verified in the circuit, split into
+/// its statement and its depth and leaf count; for a leaf the latter are the constants 0
+/// and 1.
+pub fn split_tree_info<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    depth: u32,
+    pt: &ProofWithPublicInputsTarget<D>,
+) -> (Vec<Target>, [Target; TREE_INFO_LEN]) {
+    let public_inputs = &pt.public_inputs;
+    if depth == 0 {
+        return (public_inputs.clone(), [builder.zero(), builder.one()]);
+    }
+    let split = public_inputs.len() - TREE_INFO_LEN;
+    (
+        public_inputs[..split].to_vec(),
+        [public_inputs[split], public_inputs[split + 1]],
+    )
+}
+
+/// The depth and leaf count of a proof of children of depths `depths`, given theirs. The
+/// depth of a child is fixed by its circuit, which is constant, so it is checked against
+/// the native one and the merged depth is a constant; the leaf counts are summed.
+pub fn merged_tree_info<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    depths: &[u32],
+    infos: &[[Target; TREE_INFO_LEN]],
+) -> [Target; TREE_INFO_LEN] {
+    for (&depth, info) in depths.iter().zip(infos.iter()) {
+        let depth = builder.constant(F::from_canonical_u32(depth));
+        builder.connect(info[0], depth);
+    }
+    let depth = depths.iter().max().unwrap() + 1;
+    let leaves = infos.iter().map(|info| info[1]).collect::<Vec<Target>>();
+    [
+        builder.constant(F::from_canonical_u32(depth)),
+        builder.add_many(leaves),
+    ]
+}
+
+// k-to-one aggregation. A merge circuit verifies `arity` child proofs against constant
+// verifier data, so the digest of the root circuit pins down every circuit below it.
+// What the merged proof states is up to a `MergePolicy`, which constrains the children's
+// public inputs and picks the merged proof's public inputs.
+// A wider merge verifies more proofs per circuit but needs fewer layers: with n leaves an
+// arity k tree has log_k(n) layers and (n - 1) / (k - 1) merges.
+
+pub trait MergePolicy<F: RichField + Extendable<D>, const D: usize> {
+    /// Constrains the public inputs of the children, in order, and returns those of the
+    /// merged proof. `depths` are the children's depths, 0 for a leaf, so a policy can
+    /// tell the proofs it aggregates from earlier merges.
+    fn merge(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        depths: &[u32],
+        children: &[&[Target]],
+    ) -> Vec<Target>;
+}
+
+/// Children that each go from a state to a state, the first `width` public inputs being
+/// the old state and the next `width` the new one. The new state of each child must be
+/// the old state of the next, and the merge goes from the old state of the first child to
+/// the new state of the last.
+pub struct ChainPolicy {
+    pub width: usize,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> MergePolicy<F, D> for ChainPolicy {
+    fn merge(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        _depths: &[u32],
+        children: &[&[Target]],
+    ) -> Vec<Target> {
+        let w = self.width;
+        for pair in children.windows(2) {
+            for i in 0..w {
+                builder.connect(pair[0][w + i], pair[1][i]);
+            }
+        }
+        let last = children[children.len() - 1];
+        [&children[0][..w], &last[w..2 * w]].concat()
+    }
+}
+
+/// Children whose public inputs are a list of hashes, merged slot by slot with
+/// `H::two_to_one` in a binary tree, so that each slot of the root is the root of a
+/// `MerkleTree<F, H>` over the slots of the leaves whatever the (power of two) arity.
+pub struct TwoToOnePolicy<H> {
+    _hasher: PhantomData<H>,
+}
+
+impl<H> TwoToOnePolicy<H> {
+    pub fn new() -> Self {
+        TwoToOnePolicy {
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<H> Default for TwoToOnePolicy<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize> MergePolicy<F, D>
+    for TwoToOnePolicy<H>
+{
+    fn merge(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        _depths: &[u32],
+        children: &[&[Target]],
+    ) -> Vec<Target> {
+        assert!(children.len().is_power_of_two());
+        let mut layer = children.iter().map(|c| c.to_vec()).collect::<Vec<_>>();
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| {
+                    assert_eq!(pair[0].len(), pair[1].len());
+                    pair[0]
+                        .chunks(4)
+                        .zip(pair[1].chunks(4))
+                        .flat_map(|(l, r)| {
+                            let l = HashOutTarget::from_vec(l.to_vec());
+                            let r = HashOutTarget::from_vec(r.to_vec());
+                            two_to_one_circuit::<H, F, D>(builder, l, r).elements
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+        layer.pop().unwrap()
+    }
+}
+
+/// Merges the proofs in `inner` into one whose public inputs are chosen by `policy`, then
+/// its depth and leaf count. The policy sees the children's public inputs without theirs.
+pub fn merge_proofs<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    P: MergePolicy<F, D>,
+    const D: usize,
+>(
+    policy: &P,
+    inner: &[ProofTuple<F, C, D>],
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(inner.len() >= 2, "a merge needs at least two proofs");
+    let config = proof_mode.config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let mut pw = PartialWitness::new();
+
+    let mut pts = Vec::with_capacity(inner.len());
+    for tuple in inner.iter() {
+        let pt = builder.add_virtual_proof_with_pis(&tuple.cd);
+        let inner_vdt = builder.constant_verifier_data(&tuple.vd);
+        builder.verify_proof::<C>(&pt, &inner_vdt, &tuple.cd);
+        pw.set_proof_with_pis_target::<C, D>(&pt, &tuple.proof);
+        pts.push(pt);
+    }
+
+    let depths = inner.iter().map(|t| t.depth).collect::<Vec<u32>>();
+    let (statements, infos): (Vec<_>, Vec<_>) = depths
+        .iter()
+        .zip(pts.iter())
+        .map(|(&depth, pt)| split_tree_info(&mut builder, depth, pt))
+        .unzip();
+    let children = statements
+        .iter()
+        .map(|s| s.as_slice())
+        .collect::<Vec<&[Target]>>();
+    let public_inputs = policy.merge(&mut builder, &depths, &children);
+    builder.register_public_inputs(&public_inputs);
+    let tree_info = merged_tree_info(&mut builder, &depths, &infos);
+    builder.register_public_inputs(&tree_info);
+
+    let data = builder.build::<C>();
+    let proof = data.prove(pw)?;
+    Ok(ProofTuple {
+        proof,
+        vd: data.verifier_only,
+        cd: data.common,
+        depth: inner.iter().map(|t| t.depth).max().unwrap() + 1,
+    })
+}
+
+// this function merges the proofs in order, `arity` at a time. The tree is not padded:
+// the number of proofs must be arity^k with k >= 1, so callers pick the
+// arity from the divisors of their batch size. The root merge is made in `proof_mode`,
+// the ones below it in standard mode.
+pub fn merge_tree<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    P: MergePolicy<F, D>,
+    const D: usize,
+>(
+    policy: &P,
+    proofs: &[ProofTuple<F, C, D>],
+    arity: usize,
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(arity >= 2, "arity must be at least two");
+    ensure!(
+        is_power_of(proofs.len(), arity),
+        "number of proofs must be a power of the arity, got {} proofs for arity {}",
+        proofs.len(),
+        arity
+    );
+    if proofs.len() == arity {
+        return merge_proofs(policy, proofs, proof_mode);
+    }
+    // only the root is published, so the merges below it need not blind
+    let children = proofs
+        .chunks(proofs.len() / arity)
+        .map(|chunk| merge_tree(policy, chunk, arity, ProofMode::Standard))
+        .collect::<Result<Vec<_>>>()?;
+    merge_proofs(policy, &children, proof_mode)
+}
+
+// whether n = arity^k for some k >= 1, the leaf counts `merge_tree` accepts
+pub fn is_power_of(n: usize, arity: usize) -> bool {
+    if arity < 2 {
+        return false;
+    }
+    let mut power = arity;
+    while power < n {
+        power *= arity;
+    }
+    power == n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::{Field, Sample},
+        hash::{hash_types::HashOut, merkle_tree::MerkleTree, poseidon::PoseidonHash},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    use crate::merkle::zero_hash;
+    use crate::merkle_subset::SubsetCircuit;
+    use crate::pow5::Pow5Circuit;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    // proofs of x0 -> 5 x0 -> ... -> 5^n x0
+    fn pow5_steps(n: usize, x0: F) -> Result<Vec<ProofTuple<F, C, D>>> {
+        let circuit = Pow5Circuit::<F, C, D>::new(ProofMode::Standard);
+        let mut x = x0;
+        let mut steps = Vec::new();
+        for _ in 0..n {
+            steps.push(ProofTuple::leaf(circuit.prove(x)?, &circuit.data));
+            x *= F::from_canonical_u64(5);
+        }
+        Ok(steps)
+    }
+
+    #[test]
+    fn powers() {
+        assert!(is_power_of(2, 2));
+        assert!(is_power_of(8, 2));
+        assert!(is_power_of(16, 4));
+        assert!(is_power_of(3, 3));
+        assert!(!is_power_of(1, 2));
+        assert!(!is_power_of(6, 2));
+        assert!(!is_power_of(8, 4));
+        assert!(!is_power_of(4, 1));
+        assert!(!is_power_of(4, 0));
+    }
+
+    #[test]
+    fn chain_tree() -> Result<()> {
+        let x0 = F::rand();
+        let steps = pow5_steps(4, x0)?;
+        let policy = ChainPolicy { width: 1 };
+
+        let root = merge_tree(&policy, &steps, 2, ProofMode::Standard)?;
+        assert_eq!(root.statement(), [x0, x0 * F::from_canonical_u64(625)]);
+        assert_eq!(root.depth, 2);
+        assert_eq!(root.leaves(), 4);
+        assert_eq!(root.mode(), ProofMode::Standard);
+        root.verifier_data().verify(root.proof)?;
+
+        let root = merge_tree(&policy, &steps, 4, ProofMode::ZeroKnowledge)?;
+        assert_eq!(root.statement(), [x0, x0 * F::from_canonical_u64(625)]);
+        assert_eq!(root.depth, 1);
+        assert_eq!(root.mode(), ProofMode::ZeroKnowledge);
+        root.verifier_data().verify(root.proof)?;
+
+        assert!(merge_tree(&policy, &steps[..3], 2, ProofMode::Standard).is_err());
+        assert!(merge_tree(&policy, &steps, 1, ProofMode::Standard).is_err());
+        assert!(merge_proofs(&policy, &steps[..1], ProofMode::Standard).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_broken_chain() -> Result<()> {
+        let steps = pow5_steps(3, F::rand())?;
+        // the output of the first step is not the input of the third
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            merge_proofs(
+                &ChainPolicy { width: 1 },
+                &[steps[0].clone(), steps[2].clone()],
+                ProofMode::Standard,
+            )
+        }));
+        assert!(!matches!(result, Ok(Ok(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn two_to_one_roots() -> Result<()> {
+        let circuit = SubsetCircuit::<F, C, H, D>::new(ProofMode::Standard);
+        let original = (0..4)
+            .map(|_| HashOut::<F>::rand().elements.to_vec())
+            .collect::<Vec<Vec<F>>>();
+        let mut subset = original.clone();
+        subset[2] = zero_hash::<F, H>().elements.to_vec();
+        let leaves = original
+            .iter()
+            .zip(subset.iter())
+            .map(|(o, s)| Ok(ProofTuple::leaf(circuit.prove(o, s)?, &circuit.data)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let expected = [
+            MerkleTree::<F, H>::new(original, 0).cap.0[0].elements,
+            MerkleTree::<F, H>::new(subset, 0).cap.0[0].elements,
+        ]
+        .concat();
+        let policy = TwoToOnePolicy::<H>::new();
+        for arity in [2, 4] {
+            let root = merge_tree(&policy, &leaves, arity, ProofMode::Standard)?;
+            assert_eq!(root.statement(), expected);
+            assert_eq!(root.leaves(), 4);
+            root.verifier_data().verify(root.proof)?;
+        }
+        Ok(())
+    }
+}
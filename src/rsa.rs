@@ -0,0 +1,530 @@
+use anyhow::{ensure, Result};
+use num::{BigUint, Integer, Zero};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        generator::{GeneratedValues, SimpleGenerator},
+        target::Target,
+        witness::{PartialWitness, PartitionWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, CommonCircuitData},
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+    util::serialization::{Buffer, IoResult, Read, Write},
+};
+use plonky2_ecdsa::gadgets::biguint::{
+    BigUintTarget, CircuitBuilderBiguint, GeneratedValuesBigUint, WitnessBigUint,
+};
+use plonky2_u32::{
+    gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target},
+    witness::WitnessU32,
+};
+use rand::RngCore;
+
+use crate::{
+    factor::{add_virtual_biguint_checked, mul_biguint_exact, random_prime},
+    sha256::sha256,
+    zk::{ProofMode, PublicKeyMode},
+};
+
+// RSA-2048 signature verification with PKCS#1 v1.5 padding and SHA-256:
+// "s^65537 mod n is the padded encoding of this digest".
+// s, n and every intermediate value are 64 limbs of 32 bits, least significant first.
+//
+// Public inputs are laid out as follows:
+// 0..8 is the digest as big-endian u32 words, like `sha256_circuit` outputs,
+// then either 8..72 is the modulus n or 8..12 is the commitment H(n, blinding).
+
+pub const MODULUS_BITS: usize = 2048;
+pub const NUM_LIMBS: usize = MODULUS_BITS / 32;
+pub const E: u32 = 65537;
+
+// DER encoding of the SHA-256 AlgorithmIdentifier, followed by the digest in the DigestInfo
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RsaPublicKey {
+    pub n: BigUint,
+}
+
+#[derive(Clone, Debug)]
+pub struct RsaPrivateKey {
+    pub n: BigUint,
+    pub d: BigUint,
+}
+
+impl RsaPrivateKey {
+    pub fn to_public(&self) -> RsaPublicKey {
+        RsaPublicKey { n: self.n.clone() }
+    }
+}
+
+// native helpers
+
+/// A 2048-bit key with public exponent 65537.
+pub fn keygen<R: RngCore>(rng: &mut R) -> RsaPrivateKey {
+    let e = BigUint::from(E);
+    let prime = |rng: &mut R| loop {
+        let p = random_prime(MODULUS_BITS as u64 / 2, rng);
+        // e must be invertible mod p - 1
+        if !((&p - 1u32) % &e).is_zero() {
+            return p;
+        }
+    };
+    let p = prime(rng);
+    let q = loop {
+        let q = prime(rng);
+        if q != p {
+            break q;
+        }
+    };
+    let phi = (&p - 1u32) * (&q - 1u32);
+    let d = e.modinv(&phi).unwrap();
+    RsaPrivateKey { n: p * q, d }
+}
+
+/// EMSA-PKCS1-v1_5 encoding of a SHA-256 digest as a 2048-bit integer:
+/// 0x00 0x01 0xff..0xff 0x00 DigestInfo digest.
+pub fn encode_pkcs1_v15(digest: &[u8; 32]) -> BigUint {
+    let em_len = MODULUS_BITS / 8;
+    let mut em = vec![0x00, 0x01];
+    em.resize(em_len - SHA256_DIGEST_INFO.len() - digest.len() - 1, 0xff);
+    em.push(0x00);
+    em.extend(SHA256_DIGEST_INFO);
+    em.extend(digest);
+    BigUint::from_bytes_be(&em)
+}
+
+pub fn sign(sk: &RsaPrivateKey, msg: &[u8]) -> BigUint {
+    encode_pkcs1_v15(&sha256(msg)).modpow(&sk.d, &sk.n)
+}
+
+pub fn verify_digest(pk: &RsaPublicKey, digest: &[u8; 32], signature: &BigUint) -> bool {
+    *signature < pk.n && signature.modpow(&BigUint::from(E), &pk.n) == encode_pkcs1_v15(digest)
+}
+
+pub fn verify(pk: &RsaPublicKey, msg: &[u8], signature: &BigUint) -> bool {
+    verify_digest(pk, &sha256(msg), signature)
+}
+
+// the digest as big-endian u32 words, the order of the public inputs
+pub fn digest_to_words(digest: &[u8; 32]) -> [u32; 8] {
+    core::array::from_fn(|i| u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap()))
+}
+
+fn to_limbs(x: &BigUint) -> Vec<u32> {
+    let mut limbs = x.to_u32_digits();
+    limbs.resize(NUM_LIMBS, 0);
+    limbs
+}
+
+pub fn pk_to_limbs<F: RichField>(pk: &RsaPublicKey) -> Vec<F> {
+    to_limbs(&pk.n)
+        .into_iter()
+        .map(F::from_canonical_u32)
+        .collect()
+}
+
+// Native counterpart of `modulus_commitment_circuit`.
+pub fn modulus_commitment<F: RichField>(pk: &RsaPublicKey, blinding: HashOut<F>) -> HashOut<F> {
+    let mut inputs = pk_to_limbs(pk);
+    inputs.extend(blinding.elements);
+    PoseidonHash::hash_no_pad(&inputs)
+}
+
+// circuit
+
+// Fills in the quotient and remainder of x * y by n.
+#[derive(Debug)]
+struct RsaMulModGenerator {
+    x: BigUintTarget,
+    y: BigUintTarget,
+    n: BigUintTarget,
+    q: BigUintTarget,
+    r: BigUintTarget,
+}
+
+fn write_biguint_target(dst: &mut Vec<u8>, x: &BigUintTarget) -> IoResult<()> {
+    dst.write_target_vec(&x.limbs.iter().map(|limb| limb.0).collect::<Vec<Target>>())
+}
+
+fn read_biguint_target(src: &mut Buffer) -> IoResult<BigUintTarget> {
+    let limbs = src.read_target_vec()?;
+    Ok(BigUintTarget {
+        limbs: limbs.into_iter().map(U32Target).collect(),
+    })
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for RsaMulModGenerator {
+    fn id(&self) -> String {
+        "RsaMulModGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        [&self.x, &self.y, &self.n]
+            .into_iter()
+            .flat_map(|x| x.limbs.iter().map(|limb| limb.0))
+            .collect()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let x = witness.get_biguint_target(self.x.clone());
+        let y = witness.get_biguint_target(self.y.clone());
+        let n = witness.get_biguint_target(self.n.clone());
+        let (q, r) = (x * y).div_rem(&n);
+        out_buffer.set_biguint_target(&self.q, &q);
+        out_buffer.set_biguint_target(&self.r, &r);
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        for x in [&self.x, &self.y, &self.n, &self.q, &self.r] {
+            write_biguint_target(dst, x)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        Ok(RsaMulModGenerator {
+            x: read_biguint_target(src)?,
+            y: read_biguint_target(src)?,
+            n: read_biguint_target(src)?,
+            q: read_biguint_target(src)?,
+            r: read_biguint_target(src)?,
+        })
+    }
+}
+
+// a < n, for a and n with checked limbs
+fn assert_less_than<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &BigUintTarget,
+    n: &BigUintTarget,
+) {
+    let n_at_most_a = builder.cmp_biguint(n, a);
+    builder.assert_zero(n_at_most_a.target);
+}
+
+/// x * y mod n, as x * y = q * n + r with r < n. The gadgets' own `div_rem_biguint` sums
+/// too many partial products per gate for 2048-bit operands, so q and r are hinted here
+/// and checked with `mul_biguint_exact`.
+pub fn mul_mod_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &BigUintTarget,
+    y: &BigUintTarget,
+    n: &BigUintTarget,
+) -> BigUintTarget {
+    let q = add_virtual_biguint_checked(builder, n.num_limbs());
+    let r = add_virtual_biguint_checked(builder, n.num_limbs());
+    builder.add_simple_generator(RsaMulModGenerator {
+        x: x.clone(),
+        y: y.clone(),
+        n: n.clone(),
+        q: q.clone(),
+        r: r.clone(),
+    });
+
+    let product = mul_biguint_exact(builder, x, y);
+    let qn = mul_biguint_exact(builder, &q, n);
+    let qn_plus_r = builder.add_biguint(&qn, &r);
+    builder.connect_biguint(&product, &qn_plus_r);
+    assert_less_than(builder, &r, n);
+    r
+}
+
+// Poseidon commitment to the modulus, computed inside the circuit.
+pub fn modulus_commitment_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    n: &BigUintTarget,
+    blinding: HashOutTarget,
+) -> HashOutTarget {
+    let inputs = n
+        .limbs
+        .iter()
+        .map(|limb| limb.0)
+        .chain(blinding.elements)
+        .collect::<Vec<Target>>();
+    builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs)
+}
+
+pub struct RsaTargets {
+    pub digest: [U32Target; 8],
+    pub signature: BigUintTarget,
+    pub n: BigUintTarget,
+    pub blinding: Option<HashOutTarget>,
+}
+
+/// Builds the constraints "signature is a valid PKCS#1 v1.5 signature of digest under n"
+/// and registers the public inputs according to `mode`.
+pub fn verify_rsa_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    mode: PublicKeyMode,
+) -> RsaTargets {
+    let digest: [U32Target; 8] = builder.add_virtual_u32_targets(8).try_into().unwrap();
+    for word in digest.iter() {
+        builder.range_check(word.0, 32);
+    }
+    let signature = add_virtual_biguint_checked(builder, NUM_LIMBS);
+    let n = add_virtual_biguint_checked(builder, NUM_LIMBS);
+    // n is exactly 2048 bits, as `RsaCircuit::prove` checks natively; a short modulus
+    // committed to in `Committed` mode could otherwise be factored by the prover
+    let top_bits = builder.split_le(n.limbs[NUM_LIMBS - 1].0, 32);
+    builder.assert_one(top_bits[31].target);
+    assert_less_than(builder, &signature, &n);
+
+    // e = 2^16 + 1: sixteen squarings and one multiplication
+    let mut x = signature.clone();
+    for _ in 0..16 {
+        x = mul_mod_circuit(builder, &x, &x, &n);
+    }
+    x = mul_mod_circuit(builder, &x, &signature, &n);
+
+    // the padding is constant, only the lowest 8 limbs hold the digest
+    let padding = to_limbs(&encode_pkcs1_v15(&[0; 32]));
+    let mut encoded = digest.into_iter().rev().collect::<Vec<U32Target>>();
+    encoded.extend(padding[8..].iter().map(|&limb| builder.constant_u32(limb)));
+    builder.connect_biguint(&x, &BigUintTarget { limbs: encoded });
+
+    builder.register_public_inputs(&digest.map(|word| word.0));
+    let blinding = match mode {
+        PublicKeyMode::Exposed => {
+            for limb in n.limbs.iter() {
+                builder.register_public_input(limb.0);
+            }
+            None
+        }
+        PublicKeyMode::Committed => {
+            let blinding = builder.add_virtual_hash();
+            let commitment = modulus_commitment_circuit(builder, &n, blinding);
+            builder.register_public_inputs(&commitment.elements);
+            Some(blinding)
+        }
+    };
+
+    RsaTargets {
+        digest,
+        signature,
+        n,
+        blinding,
+    }
+}
+
+pub fn set_rsa_witness<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &RsaTargets,
+    digest: &[u8; 32],
+    signature: &BigUint,
+    pk: &RsaPublicKey,
+    blinding: Option<HashOut<F>>,
+) {
+    for (&target, word) in targets.digest.iter().zip(digest_to_words(digest)) {
+        pw.set_u32_target(target, word);
+    }
+    pw.set_biguint_target(&targets.signature, signature);
+    pw.set_biguint_target(&targets.n, &pk.n);
+    if let Some(blinding_target) = targets.blinding {
+        let blinding = blinding.expect("a committed modulus needs a blinding factor");
+        pw.set_hash_target(blinding_target, blinding);
+    }
+}
+
+pub struct RsaCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub mode: PublicKeyMode,
+    pub targets: RsaTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> RsaCircuit<F, C, D> {
+    pub fn new(mode: PublicKeyMode, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = verify_rsa_circuit(&mut builder, mode);
+        let data = builder.build::<C>();
+        RsaCircuit {
+            mode,
+            targets,
+            data,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        digest: &[u8; 32],
+        signature: &BigUint,
+        pk: &RsaPublicKey,
+        blinding: Option<HashOut<F>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        // an invalid signature would only fail deep inside witness generation
+        ensure!(
+            pk.n.bits() as usize == MODULUS_BITS,
+            "modulus must be 2048 bits"
+        );
+        ensure!(verify_digest(pk, digest, signature), "invalid signature");
+        let mut pw = PartialWitness::new();
+        set_rsa_witness(&mut pw, &self.targets, digest, signature, pk, blinding);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::{
+            goldilocks_field::GoldilocksField,
+            types::{Field, PrimeField64},
+        },
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    // proves with the witness set directly, skipping the native checks of `prove`; a
+    // failed constraint panics during witness generation
+    fn proves(
+        circuit: &RsaCircuit<F, C, D>,
+        digest: &[u8; 32],
+        signature: &BigUint,
+        pk: &RsaPublicKey,
+    ) -> bool {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut pw = PartialWitness::new();
+            set_rsa_witness(&mut pw, &circuit.targets, digest, signature, pk, None);
+            circuit.data.prove(pw)
+        }));
+        matches!(result, Ok(Ok(_)))
+    }
+
+    #[test]
+    fn pkcs1_encoding() {
+        let digest = sha256(b"abc");
+        let em = encode_pkcs1_v15(&digest).to_bytes_be();
+        // the leading zero byte is dropped by to_bytes_be
+        assert_eq!(em.len(), MODULUS_BITS / 8 - 1);
+        assert_eq!(em[0], 0x01);
+        let padding_end = em.len() - 32 - SHA256_DIGEST_INFO.len() - 1;
+        assert!(em[1..padding_end].iter().all(|&b| b == 0xff));
+        assert_eq!(em[padding_end], 0x00);
+        assert_eq!(em[padding_end + 1..em.len() - 32], SHA256_DIGEST_INFO);
+        assert_eq!(em[em.len() - 32..], digest);
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let sk = keygen(&mut rng);
+        let pk = sk.to_public();
+        assert_eq!(pk.n.bits() as usize, MODULUS_BITS);
+
+        let signature = sign(&sk, b"abc");
+        assert!(verify(&pk, b"abc", &signature));
+        assert!(!verify(&pk, b"abd", &signature));
+        // s + n is the same residue, but not a canonical signature
+        assert!(!verify(&pk, b"abc", &(&signature + &pk.n)));
+    }
+
+    #[test]
+    fn limbs() {
+        let digest = sha256(b"abc");
+        assert_eq!(digest_to_words(&digest)[0], 0xba7816bf);
+        let pk = RsaPublicKey {
+            n: BigUint::from(1u32) << (MODULUS_BITS - 1),
+        };
+        let limbs = pk_to_limbs::<GoldilocksField>(&pk);
+        assert_eq!(limbs.len(), NUM_LIMBS);
+        assert_eq!(limbs[NUM_LIMBS - 1].to_canonical_u64(), 1 << 31);
+    }
+
+    #[test]
+    fn mul_mod() -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = add_virtual_biguint_checked(&mut builder, 4);
+        let y = add_virtual_biguint_checked(&mut builder, 4);
+        let n = add_virtual_biguint_checked(&mut builder, 4);
+        let r = mul_mod_circuit(&mut builder, &x, &y, &n);
+        let expected = add_virtual_biguint_checked(&mut builder, 4);
+        builder.connect_biguint(&r, &expected);
+        let data = builder.build::<C>();
+
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let n_value = random_prime(128, &mut rng);
+        let cases = [
+            (&n_value - 1u32, &n_value - 1u32),
+            (BigUint::from(3u32), BigUint::from(5u32)),
+            (BigUint::zero(), &n_value - 2u32),
+        ];
+        for (a, b) in cases {
+            let mut pw = PartialWitness::new();
+            pw.set_biguint_target(&x, &a);
+            pw.set_biguint_target(&y, &b);
+            pw.set_biguint_target(&n, &n_value);
+            pw.set_biguint_target(&expected, &((&a * &b) % &n_value));
+            data.verify(data.prove(pw)?)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let sk = keygen(&mut rng);
+        let pk = sk.to_public();
+        let digest = sha256(b"abc");
+        let signature = sign(&sk, b"abc");
+
+        let circuit = RsaCircuit::<F, C, D>::new(PublicKeyMode::Exposed, ProofMode::Standard);
+        let proof = circuit.prove(&digest, &signature, &pk, None)?;
+        let words = digest_to_words(&digest).map(F::from_canonical_u32);
+        assert_eq!(proof.public_inputs[..8], words);
+        assert_eq!(proof.public_inputs[8..], pk_to_limbs::<F>(&pk));
+        circuit.data.verify(proof)?;
+
+        // still below n, but its 65537th power is not the encoding
+        let bad = &signature + 1u32;
+        assert!(circuit.prove(&digest, &bad, &pk, None).is_err());
+        assert!(!proves(&circuit, &digest, &bad, &pk));
+        assert!(!proves(&circuit, &sha256(b"abd"), &signature, &pk));
+
+        // a valid signature under a 2046-bit modulus, only the top bit check rejects it
+        let e = BigUint::from(E);
+        let short = loop {
+            let p = random_prime(MODULUS_BITS as u64 / 2 - 1, &mut rng);
+            let q = random_prime(MODULUS_BITS as u64 / 2 - 1, &mut rng);
+            let phi = (&p - 1u32) * (&q - 1u32);
+            if p != q && phi.gcd(&e) == BigUint::from(1u32) {
+                let d = e.modinv(&phi).unwrap();
+                break RsaPrivateKey { n: p * q, d };
+            }
+        };
+        let short_signature = sign(&short, b"abc");
+        assert!(verify(&short.to_public(), b"abc", &short_signature));
+        assert!(circuit
+            .prove(&digest, &short_signature, &short.to_public(), None)
+            .is_err());
+        assert!(!proves(
+            &circuit,
+            &digest,
+            &short_signature,
+            &short.to_public()
+        ));
+        Ok(())
+    }
+}
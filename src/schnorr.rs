@@ -0,0 +1,288 @@
+use anyhow::{ensure, Result};
+use num::BigUint;
+use plonky2::{
+    field::{
+        extension::{Extendable, FieldExtension},
+        goldilocks_field::GoldilocksField,
+        types::Field,
+    },
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::BoolTarget,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+use rand::RngCore;
+
+use crate::{
+    bits::{assert_less_than_constant, split_canonical},
+    ecgfp5::{
+        add_virtual_point, assert_on_curve, connect_points, gfp5_inverse, order, point_add,
+        point_constant, point_double, point_neg, point_select, point_to_limbs, random_scalar,
+        set_point_target, GFp5, Point, PointTarget,
+    },
+    zk::ProofMode,
+};
+
+// Schnorr signatures over EcGFp5 with a Poseidon challenge:
+// sign with R = k G, e = H(R, pk, m) and s = k + e sk mod n, verify s G = R + e pk.
+// Unlike ecdsa.rs, the curve is over an extension of the circuit's field, so there is no
+// non-native arithmetic. The challenge e is the 256 bits of the hash output.
+//
+// Public inputs are the message, a Poseidon digest of 4 elements, then pk.x and pk.y.
+
+// bits of the challenge, 64 per hash element
+pub const CHALLENGE_BITS: usize = 256;
+// bits of s, enough for any scalar below n; s < n is asserted so that (R, s + n) is not
+// another valid signature
+pub const SCALAR_BITS: usize = 320;
+
+#[derive(Clone, Debug)]
+pub struct SchnorrSecretKey(pub BigUint);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchnorrPublicKey(pub Point);
+
+#[derive(Clone, Debug)]
+pub struct SchnorrSignature {
+    pub r: Point,
+    pub s: BigUint,
+}
+
+impl SchnorrSecretKey {
+    pub fn to_public(&self) -> SchnorrPublicKey {
+        SchnorrPublicKey(Point::generator().mul(&self.0))
+    }
+}
+
+// native helpers
+
+pub fn keygen<R: RngCore>(rng: &mut R) -> (SchnorrSecretKey, SchnorrPublicKey) {
+    let sk = SchnorrSecretKey(random_scalar(rng));
+    let pk = sk.to_public();
+    (sk, pk)
+}
+
+// H(R, pk, m) as an integer, the hash elements being its 64-bit limbs
+pub fn challenge<F: RichField>(r: &Point, pk: &SchnorrPublicKey, msg: HashOut<F>) -> BigUint {
+    let mut inputs = point_to_limbs::<F>(r);
+    inputs.extend(point_to_limbs::<F>(&pk.0));
+    inputs.extend(msg.elements);
+    let e = PoseidonHash::hash_no_pad(&inputs);
+    e.elements.iter().rev().fold(BigUint::default(), |acc, x| {
+        (acc << 64) + x.to_canonical_u64()
+    })
+}
+
+pub fn sign<F: RichField, R: RngCore>(
+    msg: HashOut<F>,
+    sk: &SchnorrSecretKey,
+    rng: &mut R,
+) -> SchnorrSignature {
+    let k = random_scalar(rng);
+    let r = Point::generator().mul(&k);
+    let e = challenge(&r, &sk.to_public(), msg);
+    let s = (k + e * &sk.0) % order();
+    SchnorrSignature { r, s }
+}
+
+pub fn verify<F: RichField>(
+    msg: HashOut<F>,
+    sig: &SchnorrSignature,
+    pk: &SchnorrPublicKey,
+) -> bool {
+    // pk.y = 0 is the point of order 2, with which a signature holds for every even e
+    if sig.r.is_zero || pk.0.is_zero || pk.0.y.is_zero() || !pk.0.is_on_curve() || sig.s >= order()
+    {
+        return false;
+    }
+    let e = challenge(&sig.r, pk, msg);
+    Point::generator().mul(&sig.s) == sig.r.add(&pk.0.mul(&e))
+}
+
+// The accumulator of the scalar multiplication starts at this point rather than at the
+// neutral element, which has no affine coordinates.
+fn offset() -> Point {
+    let z = [0, 1, 0, 0, 0].map(GoldilocksField::from_canonical_u64);
+    Point::hash_to_subgroup(GFp5::from_basefield_array(z))
+}
+
+// circuit
+
+pub struct SchnorrTargets {
+    pub msg: HashOutTarget,
+    pub pk: PointTarget,
+    pub r: PointTarget,
+    pub s: Vec<BoolTarget>,
+}
+
+/// Builds the constraints "sig is a valid signature of msg under pk" and registers
+/// msg and pk as public inputs.
+pub fn verify_schnorr_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> SchnorrTargets {
+    let msg = builder.add_virtual_hash();
+    let pk = add_virtual_point(builder);
+    assert_on_curve(builder, &pk);
+    // pk.y != 0, as in native `verify`: this excludes the point of order 2 and (0, 0), the
+    // limbs the neutral element would have
+    gfp5_inverse(builder, pk.y);
+    let r = add_virtual_point(builder);
+    let s = (0..SCALAR_BITS)
+        .map(|_| builder.add_virtual_bool_target_safe())
+        .collect::<Vec<BoolTarget>>();
+    assert_less_than_constant(builder, &s, &order());
+
+    let inputs = [r.to_vec(), pk.to_vec(), msg.elements.to_vec()].concat();
+    let e = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+    let e_bits = e
+        .elements
+        .iter()
+        .flat_map(|&x| split_canonical(builder, x))
+        .collect::<Vec<BoolTarget>>();
+
+    // offset - e pk, by double-and-add from the top bit
+    let mut acc = point_constant(builder, &offset());
+    let neg_pk = point_neg(builder, &pk);
+    for &bit in e_bits.iter().rev() {
+        acc = point_double(builder, &acc);
+        let sum = point_add(builder, &acc, &neg_pk);
+        acc = point_select(builder, bit, &sum, &acc);
+    }
+
+    // + s G, adding the constant 2^i G for every set bit
+    let mut power = Point::generator();
+    for &bit in s.iter() {
+        let power_target = point_constant(builder, &power);
+        let sum = point_add(builder, &acc, &power_target);
+        acc = point_select(builder, bit, &sum, &acc);
+        power = power.double();
+    }
+
+    // R = s G - e pk after removing the offset, now 2^256 times the starting point
+    let mut total_offset = offset();
+    for _ in 0..CHALLENGE_BITS {
+        total_offset = total_offset.double();
+    }
+    let neg_offset = point_constant(builder, &total_offset.neg());
+    let expected_r = point_add(builder, &acc, &neg_offset);
+    connect_points(builder, &expected_r, &r);
+
+    builder.register_public_inputs(&msg.elements);
+    builder.register_public_inputs(&pk.to_vec());
+
+    SchnorrTargets { msg, pk, r, s }
+}
+
+pub fn set_schnorr_witness<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &SchnorrTargets,
+    msg: HashOut<F>,
+    sig: &SchnorrSignature,
+    pk: &SchnorrPublicKey,
+) {
+    pw.set_hash_target(targets.msg, msg);
+    set_point_target(pw, &targets.pk, &pk.0);
+    set_point_target(pw, &targets.r, &sig.r);
+    for (i, &bit) in targets.s.iter().enumerate() {
+        pw.set_bool_target(bit, sig.s.bit(i as u64));
+    }
+}
+
+pub struct SchnorrCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub targets: SchnorrTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    SchnorrCircuit<F, C, D>
+{
+    pub fn new(proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = verify_schnorr_circuit(&mut builder);
+        let data = builder.build::<C>();
+        SchnorrCircuit { targets, data }
+    }
+
+    pub fn prove(
+        &self,
+        msg: HashOut<F>,
+        sig: &SchnorrSignature,
+        pk: &SchnorrPublicKey,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        // an invalid signature would only fail deep inside witness generation
+        ensure!(verify(msg, sig, pk), "invalid signature");
+        let mut pw = PartialWitness::new();
+        set_schnorr_witness(&mut pw, &self.targets, msg, sig, pk);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{field::types::Sample, plonk::config::PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn signed() -> (HashOut<F>, SchnorrSignature, SchnorrPublicKey) {
+        let mut rng = rand::thread_rng();
+        let (sk, pk) = keygen(&mut rng);
+        let msg = HashOut::<F>::rand();
+        let sig = sign(msg, &sk, &mut rng);
+        (msg, sig, pk)
+    }
+
+    #[test]
+    fn native_rejects_malleated_s() {
+        let (msg, sig, pk) = signed();
+        assert!(verify(msg, &sig, &pk));
+        let malleated = SchnorrSignature {
+            r: sig.r,
+            s: &sig.s + order(),
+        };
+        assert!(!verify(msg, &malleated, &pk));
+    }
+
+    #[test]
+    fn native_rejects_point_of_order_two() {
+        let (msg, sig, _) = signed();
+        let pk = SchnorrPublicKey(Point {
+            x: GFp5::ZERO,
+            y: GFp5::ZERO,
+            is_zero: false,
+        });
+        assert!(pk.0.is_on_curve());
+        assert!(!verify(msg, &sig, &pk));
+        assert!(!verify(msg, &sig, &SchnorrPublicKey(Point::ZERO)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn circuit_rejects_malleated_s() {
+        let (msg, sig, pk) = signed();
+        let malleated = SchnorrSignature {
+            r: sig.r,
+            s: &sig.s + order(),
+        };
+        assert!(malleated.s.bits() <= SCALAR_BITS as u64);
+        let circuit = SchnorrCircuit::<F, C, D>::new(ProofMode::Standard);
+        let mut pw = PartialWitness::new();
+        // bypasses the native check in `prove`
+        set_schnorr_witness(&mut pw, &circuit.targets, msg, &malleated, &pk);
+        let _ = circuit.data.prove(pw);
+    }
+}
@@ -0,0 +1,220 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_tree::MerkleTree,
+    },
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::merkle::{
+    add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness, MerkleProofTargets,
+};
+use crate::zk::ProofMode;
+
+// Semaphore-style group membership: "I know the secret behind one of the leaves
+// of this tree, and this is my nullifier for the given external nullifier".
+// The same member signalling twice under one external nullifier gives the same
+// nullifier, while nullifiers under different external nullifiers are unlinkable.
+//
+// Public inputs are laid out as follows:
+// 0..4 is the root, 4..8 the external nullifier, 8..12 the nullifier.
+
+pub struct SemaphoreTargets {
+    pub secret: HashOutTarget,
+    pub merkle_proof: MerkleProofTargets,
+    pub root: HashOutTarget,
+    pub external_nullifier: HashOutTarget,
+    pub nullifier: HashOutTarget,
+}
+
+pub fn semaphore_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> SemaphoreTargets {
+    let secret = builder.add_virtual_hash();
+    let merkle_proof = add_virtual_merkle_proof(builder, height);
+    let root = builder.add_virtual_hash();
+    let external_nullifier = builder.add_virtual_hash();
+
+    // leaf = H(secret)
+    let leaf = builder.hash_n_to_hash_no_pad::<H>(secret.elements.to_vec());
+    let computed_root = merkle_root_circuit::<H, F, D>(builder, leaf, &merkle_proof);
+    builder.connect_hashes(computed_root, root);
+
+    // nullifier = H(external_nullifier, secret)
+    // The external nullifier goes first: with the secret first, a zero external
+    // nullifier would make the nullifier equal to the leaf.
+    let inputs = [external_nullifier.elements, secret.elements].concat();
+    let nullifier = builder.hash_n_to_hash_no_pad::<H>(inputs);
+
+    builder.register_public_inputs(&root.elements);
+    builder.register_public_inputs(&external_nullifier.elements);
+    builder.register_public_inputs(&nullifier.elements);
+
+    SemaphoreTargets {
+        secret,
+        merkle_proof,
+        root,
+        external_nullifier,
+        nullifier,
+    }
+}
+
+// native helpers
+
+pub fn identity_commitment<F: RichField, H: AlgebraicHasher<F>>(secret: HashOut<F>) -> HashOut<F> {
+    H::hash_no_pad(&secret.elements)
+}
+
+pub fn nullifier<F: RichField, H: AlgebraicHasher<F>>(
+    secret: HashOut<F>,
+    external_nullifier: HashOut<F>,
+) -> HashOut<F> {
+    let inputs = [external_nullifier.elements, secret.elements].concat();
+    H::hash_no_pad(&inputs)
+}
+
+// builds the group tree, the number of secrets must be a power of two
+pub fn identity_tree<F: RichField, H: AlgebraicHasher<F>>(
+    secrets: &[HashOut<F>],
+) -> MerkleTree<F, H> {
+    let leaves = secrets
+        .iter()
+        .map(|secret| identity_commitment::<F, H>(*secret).elements.to_vec())
+        .collect::<Vec<Vec<F>>>();
+    MerkleTree::new(leaves, 0)
+}
+
+pub struct SemaphoreCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub targets: SemaphoreTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > SemaphoreCircuit<F, C, H, D>
+{
+    pub fn new(height: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = semaphore_circuit::<H, F, D>(&mut builder, height);
+        let data = builder.build::<C>();
+        SemaphoreCircuit {
+            targets,
+            data,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        tree: &MerkleTree<F, H>,
+        secret: HashOut<F>,
+        leaf_index: usize,
+        external_nullifier: HashOut<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(leaf_index < tree.leaves.len(), "leaf index out of range");
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(self.targets.secret, secret);
+        set_merkle_proof_witness(
+            &mut pw,
+            &self.targets.merkle_proof,
+            leaf_index,
+            &tree.prove(leaf_index),
+        );
+        pw.set_hash_target(self.targets.root, tree.cap.0[0]);
+        pw.set_hash_target(self.targets.external_nullifier, external_nullifier);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::{Field, Sample},
+        hash::poseidon::PoseidonHash,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    const HEIGHT: usize = 3;
+
+    fn secrets() -> Vec<HashOut<F>> {
+        (0..1 << HEIGHT)
+            .map(|i| HashOut::from_partial(&[F::from_canonical_u64(i), F::ONE]))
+            .collect()
+    }
+
+    #[test]
+    fn nullifiers() {
+        let [a, b] = [HashOut::<F>::rand(), HashOut::<F>::rand()];
+        let topic = HashOut::rand();
+        assert_eq!(nullifier::<F, H>(a, topic), nullifier::<F, H>(a, topic));
+        assert_ne!(nullifier::<F, H>(a, topic), nullifier::<F, H>(b, topic));
+        assert_ne!(
+            nullifier::<F, H>(a, topic),
+            nullifier::<F, H>(a, HashOut::rand())
+        );
+        assert_ne!(
+            nullifier::<F, H>(a, HashOut::ZERO),
+            identity_commitment::<F, H>(a)
+        );
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let secrets = secrets();
+        let tree = identity_tree::<F, H>(&secrets);
+        let root = tree.cap.0[0];
+        let topic = HashOut::from_partial(&[F::TWO]);
+
+        let circuit = SemaphoreCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+        let proof = circuit.prove(&tree, secrets[5], 5, topic)?;
+        let expected = [
+            root.elements,
+            topic.elements,
+            nullifier::<F, H>(secrets[5], topic).elements,
+        ]
+        .concat();
+        assert_eq!(proof.public_inputs, expected);
+        circuit.data.verify(proof)?;
+
+        assert!(circuit
+            .prove(&tree, secrets[5], 1 << HEIGHT, topic)
+            .is_err());
+        // a secret that is not in the group, or not at that leaf, misses the root
+        for (secret, index) in [(HashOut::from_partial(&[F::TWO]), 5), (secrets[5], 4)] {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                circuit.prove(&tree, secret, index, topic)
+            }));
+            assert!(!matches!(result, Ok(Ok(_))));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,457 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_u32::gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target};
+
+use crate::bits::{bits_to_u32, u32_to_bits, xor};
+use crate::zk::ProofMode;
+
+// SHA-256 (FIPS 180-4) over u32 limbs. Words are U32Targets, additions mod 2^32 use
+// the u32 add-many gate and the bitwise functions work on the little-endian bits of a word.
+// Messages have a variable length up to `max_len` bytes: the padding is built in the
+// circuit from the length, and the digest is the state after the last padded block.
+
+pub const DIGEST_WORDS: usize = 8;
+const BLOCK_BYTES: usize = 64;
+
+const IV: [u32; DIGEST_WORDS] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+pub type Sha256DigestTarget = [U32Target; DIGEST_WORDS];
+
+// number of blocks needed for a message of up to max_len bytes
+pub fn num_blocks(max_len: usize) -> usize {
+    (max_len + 9).div_ceil(BLOCK_BYTES)
+}
+
+// big-endian bytes of the digest words
+pub fn digest_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+// native SHA-256, the same computation as `sha256_circuit`
+pub fn sha256(message: &[u8]) -> [u8; 32] {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    padded.resize(num_blocks(message.len()) * BLOCK_BYTES - 8, 0);
+    padded.extend((8 * message.len() as u64).to_be_bytes());
+
+    let mut state = IV;
+    for block in padded.chunks(BLOCK_BYTES) {
+        let mut w = block
+            .chunks(4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<u32>>();
+        for t in 16..64 {
+            let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+            let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+            w.push(
+                w[t - 16]
+                    .wrapping_add(s0)
+                    .wrapping_add(w[t - 7])
+                    .wrapping_add(s1),
+            );
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for t in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(ROUND_CONSTANTS[t])
+                .wrapping_add(w[t]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+    digest_to_bytes(&state).try_into().unwrap()
+}
+
+// a word together with its bits
+struct Word {
+    value: U32Target,
+    bits: Vec<BoolTarget>,
+}
+
+fn word<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    value: U32Target,
+) -> Word {
+    let bits = u32_to_bits(builder, value);
+    Word { value, bits }
+}
+
+fn rotr(bits: &[BoolTarget], n: usize) -> Vec<BoolTarget> {
+    (0..32).map(|i| bits[(i + n) % 32]).collect()
+}
+
+fn shr<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    bits: &[BoolTarget],
+    n: usize,
+) -> Vec<BoolTarget> {
+    (0..32)
+        .map(|i| if i + n < 32 { bits[i + n] } else { builder._false() })
+        .collect()
+}
+
+fn xor3<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &[BoolTarget],
+    y: &[BoolTarget],
+    z: &[BoolTarget],
+) -> U32Target {
+    let bits = (0..32)
+        .map(|i| {
+            let t = xor(builder, x[i], y[i]);
+            xor(builder, t, z[i])
+        })
+        .collect::<Vec<BoolTarget>>();
+    bits_to_u32(builder, &bits)
+}
+
+// Σ0 and Σ1
+fn big_sigma<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &Word,
+    rotations: [usize; 3],
+) -> U32Target {
+    let [r0, r1, r2] = rotations.map(|r| rotr(&x.bits, r));
+    xor3(builder, &r0, &r1, &r2)
+}
+
+// σ0 and σ1
+fn small_sigma<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &Word,
+    rotations: [usize; 2],
+    shift: usize,
+) -> U32Target {
+    let [r0, r1] = rotations.map(|r| rotr(&x.bits, r));
+    let s = shr(builder, &x.bits, shift);
+    xor3(builder, &r0, &r1, &s)
+}
+
+// (e & f) ^ (!e & g) = g + e(f - g)
+fn ch<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    e: &Word,
+    f: &Word,
+    g: &Word,
+) -> U32Target {
+    let bits = (0..32)
+        .map(|i| {
+            let diff = builder.sub(f.bits[i].target, g.bits[i].target);
+            BoolTarget::new_unsafe(builder.mul_add(e.bits[i].target, diff, g.bits[i].target))
+        })
+        .collect::<Vec<BoolTarget>>();
+    bits_to_u32(builder, &bits)
+}
+
+// (a & b) ^ (a & c) ^ (b & c) = ab + c(a ^ b)
+fn maj<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &Word,
+    b: &Word,
+    c: &Word,
+) -> U32Target {
+    let bits = (0..32)
+        .map(|i| {
+            let ab = builder.mul(a.bits[i].target, b.bits[i].target);
+            let a_xor_b = xor(builder, a.bits[i], b.bits[i]);
+            BoolTarget::new_unsafe(builder.mul_add(c.bits[i].target, a_xor_b.target, ab))
+        })
+        .collect::<Vec<BoolTarget>>();
+    bits_to_u32(builder, &bits)
+}
+
+// sum mod 2^32
+fn add_mod<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    terms: &[U32Target],
+) -> U32Target {
+    builder.add_many_u32(terms).0
+}
+
+// one SHA-256 compression of a block of 16 words
+fn compress<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: &Sha256DigestTarget,
+    block: &[U32Target],
+) -> Sha256DigestTarget {
+    // message schedule
+    let mut w = block
+        .iter()
+        .map(|&x| word(builder, x))
+        .collect::<Vec<Word>>();
+    for t in 16..64 {
+        let s0 = small_sigma(builder, &w[t - 15], [7, 18], 3);
+        let s1 = small_sigma(builder, &w[t - 2], [17, 19], 10);
+        let value = add_mod(builder, &[w[t - 16].value, s0, w[t - 7].value, s1]);
+        w.push(word(builder, value));
+    }
+
+    // working variables a..h
+    let mut vars = state
+        .iter()
+        .map(|&x| word(builder, x))
+        .collect::<Vec<Word>>();
+    for (t, &round_constant) in ROUND_CONSTANTS.iter().enumerate() {
+        let s1 = big_sigma(builder, &vars[4], [6, 11, 25]);
+        let ch = ch(builder, &vars[4], &vars[5], &vars[6]);
+        let k = builder.constant_u32(round_constant);
+        let s0 = big_sigma(builder, &vars[0], [2, 13, 22]);
+        let maj = maj(builder, &vars[0], &vars[1], &vars[2]);
+
+        // t1 = h + Σ1(e) + ch + k + w, e' = d + t1, a' = t1 + Σ0(a) + maj
+        let t1 = [vars[7].value, s1, ch, k, w[t].value];
+        let new_e = add_mod(builder, &[&t1[..], &[vars[3].value]].concat());
+        let new_a = add_mod(builder, &[&t1[..], &[s0, maj]].concat());
+
+        // (a, b, c, d, e, f, g, h) <- (a', a, b, c, e', e, f, g)
+        vars.pop();
+        vars.remove(3);
+        let new_e = word(builder, new_e);
+        vars.insert(3, new_e);
+        let new_a = word(builder, new_a);
+        vars.insert(0, new_a);
+    }
+
+    core::array::from_fn(|i| add_mod(builder, &[state[i], vars[i].value]))
+}
+
+/// SHA-256 of the first `length` bytes of `message`, where `message.len()` is the maximum length.
+/// Every byte is range checked, bytes past `length` are ignored.
+pub fn sha256_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    message: &[Target],
+    length: Target,
+) -> Sha256DigestTarget {
+    let max_len = message.len();
+    // the bit length has to fit in a u32
+    assert!(max_len < 1 << 29, "message too long");
+    let num_blocks = num_blocks(max_len);
+
+    for &byte in message {
+        builder.range_check(byte, 8);
+    }
+
+    // is_end[j] is set iff length == j, exactly one is set so length <= max_len
+    let is_end = (0..=max_len)
+        .map(|j| {
+            let j = builder.constant(F::from_canonical_usize(j));
+            builder.is_equal(length, j)
+        })
+        .collect::<Vec<BoolTarget>>();
+    let count = builder.add_many(is_end.iter().map(|b| b.target));
+    builder.assert_one(count);
+
+    // block k is the last one iff 64k - 8 <= length <= 64k + 55
+    let is_last = (0..num_blocks)
+        .map(|k| {
+            let first = (BLOCK_BYTES * k).saturating_sub(8);
+            let last = (BLOCK_BYTES * k + 55).min(max_len);
+            let flags = is_end[first..=last]
+                .iter()
+                .map(|b| b.target)
+                .collect::<Vec<Target>>();
+            builder.add_many(flags)
+        })
+        .collect::<Vec<Target>>();
+
+    // bit length as 8 big-endian bytes, the top 4 are zero
+    let bit_len = builder.mul_const(F::from_canonical_u64(8), length);
+    let bit_len_bits = builder.split_le(bit_len, 32);
+    let zero = builder.zero();
+    let mut len_bytes = vec![zero; 4];
+    for i in (0..4).rev() {
+        len_bytes.push(builder.le_sum(bit_len_bits[8 * i..8 * i + 8].iter()));
+    }
+
+    // padded message: the bytes before length, 0x80, zeros, then the bit length
+    // at the end of the last block
+    let mut in_message = builder.one();
+    let padded = (0..num_blocks * BLOCK_BYTES)
+        .map(|i| {
+            let mut byte = zero;
+            if i <= max_len {
+                in_message = builder.sub(in_message, is_end[i].target);
+                byte = builder.mul_const(F::from_canonical_u8(0x80), is_end[i].target);
+            }
+            if i < max_len {
+                byte = builder.mul_add(in_message, message[i], byte);
+            }
+            if i % BLOCK_BYTES >= 56 {
+                let len_byte = len_bytes[i % BLOCK_BYTES - 56];
+                byte = builder.mul_add(is_last[i / BLOCK_BYTES], len_byte, byte);
+            }
+            byte
+        })
+        .collect::<Vec<Target>>();
+
+    // big-endian words
+    let words = padded
+        .chunks(4)
+        .map(|bytes| {
+            let mut value = bytes[0];
+            for &byte in &bytes[1..] {
+                value = builder.mul_const_add(F::from_canonical_u64(256), value, byte);
+            }
+            U32Target(value)
+        })
+        .collect::<Vec<U32Target>>();
+
+    // run every block and keep the state after the last one
+    let mut state = IV.map(|x| builder.constant_u32(x));
+    let mut digest = [zero; DIGEST_WORDS];
+    for (k, block) in words.chunks(16).enumerate() {
+        state = compress(builder, &state, block);
+        for i in 0..DIGEST_WORDS {
+            digest[i] = builder.mul_add(is_last[k], state[i].0, digest[i]);
+        }
+    }
+    digest.map(U32Target)
+}
+
+// "I know x such that SHA256(x) = h", with x of at most max_len bytes.
+// The public inputs are the 8 digest words.
+pub struct Sha256Targets {
+    pub message: Vec<Target>,
+    pub length: Target,
+    pub digest: Sha256DigestTarget,
+}
+
+pub fn sha256_preimage_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    max_len: usize,
+) -> Sha256Targets {
+    let message = builder.add_virtual_targets(max_len);
+    let length = builder.add_virtual_target();
+    let digest = sha256_circuit(builder, &message, length);
+    builder.register_public_inputs(&digest.map(|word| word.0));
+    Sha256Targets {
+        message,
+        length,
+        digest,
+    }
+}
+
+pub struct Sha256Circuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub targets: Sha256Targets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> Sha256Circuit<F, C, D> {
+    pub fn new(max_len: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = sha256_preimage_circuit(&mut builder, max_len);
+        let data = builder.build::<C>();
+        Sha256Circuit { targets, data }
+    }
+
+    pub fn prove(&self, message: &[u8]) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(message.len() <= self.targets.message.len(), "message too long");
+        let mut pw = PartialWitness::new();
+        for (i, &target) in self.targets.message.iter().enumerate() {
+            let byte = message.get(i).copied().unwrap_or(0);
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        pw.set_target(self.targets.length, F::from_canonical_usize(message.len()));
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{field::types::Field, plonk::config::PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    // FIPS 180-4 examples: one block, empty, and a message whose padding spills into a
+    // second block
+    const VECTORS: [(&[u8], [u32; DIGEST_WORDS]); 3] = [
+        (
+            b"abc",
+            [
+                0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+                0xf20015ad,
+            ],
+        ),
+        (
+            b"",
+            [
+                0xe3b0c442, 0x98fc1c14, 0x9afbf4c8, 0x996fb924, 0x27ae41e4, 0x649b934c, 0xa495991b,
+                0x7852b855,
+            ],
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            [
+                0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
+                0x19db06c1,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn native_known_answers() {
+        for (message, words) in VECTORS {
+            assert_eq!(sha256(message).to_vec(), digest_to_bytes(&words));
+        }
+    }
+
+    #[test]
+    fn circuit_known_answers() -> Result<()> {
+        // one circuit for every length up to 64 bytes, i.e. two blocks
+        let circuit = Sha256Circuit::<F, C, D>::new(64, ProofMode::Standard);
+        for (message, words) in VECTORS {
+            let proof = circuit.prove(message)?;
+            let expected = words.map(F::from_canonical_u32);
+            assert_eq!(proof.public_inputs, expected);
+            circuit.data.verify(proof)?;
+        }
+        Ok(())
+    }
+}
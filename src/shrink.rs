@@ -0,0 +1,120 @@
+use anyhow::Result;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitConfig,
+        config::{AlgebraicHasher, GenericConfig},
+    },
+};
+
+use crate::recursion::ProofTuple;
+use crate::zk::ProofMode;
+
+// A last recursive layer that re-proves a root proof for size rather than for further
+// recursion. `standard_recursion_config` keeps the blowup small so that a verifier circuit
+// stays cheap, and pays for it with 28 FRI queries. The wrapper verifies the root once
+// and is proven with a 2^7 blowup and 12 queries: 12 * 7 + 16 bits of grinding give the
+// same 100 bits of conjectured security as 28 * 3 + 16, with fewer than half the
+// Merkle paths. Proving takes longer since the LDE is 16 times larger.
+//
+// The wrapper may use another config than the root, e.g. `KeccakGoldilocksConfig`, whose
+// proofs are cheaper to check outside plonky2 but can no longer be verified in a circuit.
+// Public inputs are those of the root proof, and so are its depth and leaf count: the
+// wrapper adds no layer to the tree.
+
+pub const SHRINK_RATE_BITS: usize = 7;
+pub const SHRINK_QUERY_ROUNDS: usize = 12;
+
+/// The high-rate config of the wrapper, in `proof_mode`.
+pub fn shrink_config(proof_mode: ProofMode) -> CircuitConfig {
+    let mut config = proof_mode.config();
+    config.fri_config.rate_bits = SHRINK_RATE_BITS;
+    config.fri_config.num_query_rounds = SHRINK_QUERY_ROUNDS;
+    config
+}
+
+/// Re-proves `inner` in `shrink_config(proof_mode)` with the config C2.
+pub fn shrink_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C2: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inner: &ProofTuple<F, C, D>,
+    proof_mode: ProofMode,
+) -> Result<ProofTuple<F, C2, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(shrink_config(proof_mode));
+
+    let pt = builder.add_virtual_proof_with_pis(&inner.cd);
+    // constant inner verifier data, so only proofs of the root circuit are accepted
+    let inner_vdt = builder.constant_verifier_data(&inner.vd);
+    builder.verify_proof::<C>(&pt, &inner_vdt, &inner.cd);
+    builder.register_public_inputs(&pt.public_inputs);
+
+    let mut pw = PartialWitness::new();
+    pw.set_proof_with_pis_target::<C, D>(&pt, &inner.proof);
+
+    let data = builder.build::<C2>();
+    let proof = data.prove(pw)?;
+    Ok(ProofTuple {
+        proof,
+        vd: data.verifier_only,
+        cd: data.common,
+        depth: inner.depth,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use crate::pow5::Pow5Circuit;
+    use crate::recursion::{merge_proofs, ChainPolicy};
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn shrink_round_trip() -> Result<()> {
+        let circuit = Pow5Circuit::<F, C, D>::new(ProofMode::Standard);
+        let x = F::from_canonical_u64(3);
+        let leaves = [
+            ProofTuple::leaf(circuit.prove(x)?, &circuit.data),
+            ProofTuple::leaf(circuit.prove(x * F::from_canonical_u64(5))?, &circuit.data),
+        ];
+        let root = merge_proofs(&ChainPolicy { width: 1 }, &leaves, ProofMode::Standard)?;
+
+        let shrunk = shrink_proof::<F, C, C, D>(&root, ProofMode::Standard)?;
+        assert_eq!(shrunk.proof.public_inputs, root.proof.public_inputs);
+        assert_eq!(shrunk.depth, root.depth);
+        assert_eq!(shrunk.leaves(), 2);
+        assert_eq!(shrunk.cd.config.fri_config.rate_bits, SHRINK_RATE_BITS);
+        assert_eq!(
+            shrunk.proof.proof.opening_proof.query_round_proofs.len(),
+            SHRINK_QUERY_ROUNDS
+        );
+        shrunk.verifier_data().verify(shrunk.proof.clone())?;
+
+        // the wrapper only proves if the root proof verifies
+        let mut tampered = root.clone();
+        tampered.proof.public_inputs[1] += F::ONE;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            shrink_proof::<F, C, C, D>(&tampered, ProofMode::Standard)
+        }));
+        assert!(!matches!(result, Ok(Ok(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::{HashOut, HashOutTarget, RichField},
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::bits::split_canonical;
+use crate::merkle::{merkle_root_from_bits, zero_hash};
+use crate::zk::ProofMode;
+
+// Sparse Merkle tree keyed by `key_len` field elements, 64 path bits per element.
+// A field key uses key_len = 1, a Poseidon digest key_len = 4. Keys are field elements,
+// so 4 Goldilocks elements cover less than 256 bits: a raw 256-bit value (e.g. an address
+// or a Keccak digest) must be hashed or split into 32-bit limbs first.
+// The path of a key is its little-endian bits, bit 0 being the bottom layer.
+// Absent keys have the empty leaf zero_hash(), present keys H(1, key, value).
+// The leading 1 keeps H(key, value) from ever equaling the empty leaf.
+
+pub struct SparseMerkleTree<F: RichField, H: AlgebraicHasher<F>> {
+    pub key_len: usize,
+    // non-empty nodes, keyed by (layer, the path bits above that layer)
+    nodes: HashMap<(usize, Vec<bool>), HashOut<F>>,
+    values: HashMap<Vec<F>, HashOut<F>>,
+    // root of an empty subtree for every layer
+    empty: Vec<HashOut<F>>,
+    _hasher: PhantomData<H>,
+}
+
+// siblings from the bottom layer up
+pub struct SmtProof<F: RichField> {
+    pub siblings: Vec<HashOut<F>>,
+}
+
+pub fn key_bits<F: RichField>(key: &[F]) -> Vec<bool> {
+    key.iter()
+        .flat_map(|k| {
+            let k = k.to_canonical_u64();
+            (0..64).map(move |i| (k >> i) & 1 == 1)
+        })
+        .collect()
+}
+
+pub fn leaf_hash<F: RichField, H: AlgebraicHasher<F>>(key: &[F], value: HashOut<F>) -> HashOut<F> {
+    let inputs = [&[F::ONE], key, &value.elements].concat();
+    H::hash_no_pad(&inputs)
+}
+
+impl<F: RichField, H: AlgebraicHasher<F>> SparseMerkleTree<F, H> {
+    pub fn new(key_len: usize) -> Self {
+        let mut empty = vec![zero_hash::<F, H>()];
+        for i in 0..64 * key_len {
+            empty.push(H::two_to_one(empty[i], empty[i]));
+        }
+        SparseMerkleTree {
+            key_len,
+            nodes: HashMap::new(),
+            values: HashMap::new(),
+            empty,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        64 * self.key_len
+    }
+
+    fn node(&self, layer: usize, path: &[bool]) -> HashOut<F> {
+        *self
+            .nodes
+            .get(&(layer, path.to_vec()))
+            .unwrap_or(&self.empty[layer])
+    }
+
+    pub fn root(&self) -> HashOut<F> {
+        self.node(self.depth(), &[])
+    }
+
+    pub fn get(&self, key: &[F]) -> Option<HashOut<F>> {
+        self.values.get(key).copied()
+    }
+
+    // writes the leaf and recomputes the nodes above it
+    fn set_leaf(&mut self, key: &[F], leaf: HashOut<F>) {
+        let bits = key_bits(key);
+        let mut current = leaf;
+        for layer in 0..self.depth() {
+            if current == self.empty[layer] {
+                self.nodes.remove(&(layer, bits[layer..].to_vec()));
+            } else {
+                self.nodes.insert((layer, bits[layer..].to_vec()), current);
+            }
+            let mut sibling_path = bits[layer..].to_vec();
+            sibling_path[0] = !sibling_path[0];
+            let sibling = self.node(layer, &sibling_path);
+            current = if bits[layer] {
+                H::two_to_one(sibling, current)
+            } else {
+                H::two_to_one(current, sibling)
+            };
+        }
+        self.nodes.insert((self.depth(), Vec::new()), current);
+    }
+
+    pub fn insert(&mut self, key: &[F], value: HashOut<F>) -> Result<()> {
+        ensure!(key.len() == self.key_len, "wrong key length");
+        ensure!(!self.values.contains_key(key), "key is already in the tree");
+        self.values.insert(key.to_vec(), value);
+        self.set_leaf(key, leaf_hash::<F, H>(key, value));
+        Ok(())
+    }
+
+    pub fn update(&mut self, key: &[F], value: HashOut<F>) -> Result<()> {
+        ensure!(self.values.contains_key(key), "key is not in the tree");
+        self.values.insert(key.to_vec(), value);
+        self.set_leaf(key, leaf_hash::<F, H>(key, value));
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: &[F]) -> Result<HashOut<F>> {
+        let value = self
+            .values
+            .remove(key)
+            .ok_or_else(|| anyhow!("key is not in the tree"))?;
+        self.set_leaf(key, self.empty[0]);
+        Ok(value)
+    }
+
+    // the path of a key, valid both for present and absent keys
+    pub fn prove(&self, key: &[F]) -> Result<SmtProof<F>> {
+        ensure!(key.len() == self.key_len, "wrong key length");
+        let bits = key_bits(key);
+        let siblings = (0..self.depth())
+            .map(|layer| {
+                let mut sibling_path = bits[layer..].to_vec();
+                sibling_path[0] = !sibling_path[0];
+                self.node(layer, &sibling_path)
+            })
+            .collect();
+        Ok(SmtProof { siblings })
+    }
+}
+
+/// What the proof states about the key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtStatement {
+    /// key maps to value, public inputs are (root, key, value).
+    Membership,
+    /// key is not in the tree, public inputs are (root, key).
+    NonMembership,
+}
+
+pub struct SmtTargets {
+    pub key: Vec<Target>,
+    pub value: Option<HashOutTarget>,
+    pub siblings: Vec<HashOutTarget>,
+    pub root: HashOutTarget,
+}
+
+pub fn smt_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key_len: usize,
+    statement: SmtStatement,
+) -> SmtTargets {
+    let key = builder.add_virtual_targets(key_len);
+    let siblings = builder.add_virtual_hashes(64 * key_len);
+    let root = builder.add_virtual_hash();
+
+    let bits = key
+        .iter()
+        .flat_map(|&k| split_canonical(builder, k))
+        .collect::<Vec<BoolTarget>>();
+
+    let (leaf, value) = match statement {
+        SmtStatement::Membership => {
+            let value = builder.add_virtual_hash();
+            let one = builder.one();
+            let inputs = [&[one], key.as_slice(), &value.elements].concat();
+            let leaf = builder.hash_n_to_hash_no_pad::<H>(inputs);
+            (leaf, Some(value))
+        }
+        SmtStatement::NonMembership => {
+            let leaf = builder.constant_hash(zero_hash::<F, H>());
+            (leaf, None)
+        }
+    };
+    let computed_root = merkle_root_from_bits::<H, F, D>(builder, leaf, &bits, &siblings);
+    builder.connect_hashes(computed_root, root);
+
+    builder.register_public_inputs(&root.elements);
+    builder.register_public_inputs(&key);
+    if let Some(value) = value {
+        builder.register_public_inputs(&value.elements);
+    }
+
+    SmtTargets {
+        key,
+        value,
+        siblings,
+        root,
+    }
+}
+
+pub struct SmtCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub statement: SmtStatement,
+    pub targets: SmtTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > SmtCircuit<F, C, H, D>
+{
+    pub fn new(key_len: usize, statement: SmtStatement, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = smt_circuit::<H, F, D>(&mut builder, key_len, statement);
+        let data = builder.build::<C>();
+        SmtCircuit {
+            statement,
+            targets,
+            data,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        tree: &SparseMerkleTree<F, H>,
+        key: &[F],
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            tree.key_len == self.targets.key.len(),
+            "tree has another key length than the circuit"
+        );
+        let proof = tree.prove(key)?;
+        let value = tree.get(key);
+        match self.statement {
+            SmtStatement::Membership => ensure!(value.is_some(), "key is not in the tree"),
+            SmtStatement::NonMembership => ensure!(value.is_none(), "key is in the tree"),
+        }
+
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&self.targets.key, key);
+        if let (Some(target), Some(value)) = (self.targets.value, value) {
+            pw.set_hash_target(target, value);
+        }
+        for (&target, &sibling) in self.targets.siblings.iter().zip(proof.siblings.iter()) {
+            pw.set_hash_target(target, sibling);
+        }
+        pw.set_hash_target(self.targets.root, tree.root());
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::{Field, Sample},
+        hash::poseidon::PoseidonHash,
+        plonk::config::{GenericConfig, Hasher, PoseidonGoldilocksConfig},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    // the root from a leaf and its path, as the circuit computes it
+    fn fold(key: &[F], leaf: HashOut<F>, proof: &SmtProof<F>) -> HashOut<F> {
+        key_bits(key).into_iter().zip(proof.siblings.iter()).fold(
+            leaf,
+            |current, (bit, &sibling)| {
+                if bit {
+                    H::two_to_one(sibling, current)
+                } else {
+                    H::two_to_one(current, sibling)
+                }
+            },
+        )
+    }
+
+    fn keys() -> Vec<[F; 1]> {
+        let mut keys = [0, 1, 2, 3, 1 << 40]
+            .map(|k| [F::from_canonical_u64(k)])
+            .to_vec();
+        keys.push([F::NEG_ONE]);
+        keys
+    }
+
+    #[test]
+    fn empty_root() {
+        let tree = SparseMerkleTree::<F, H>::new(1);
+        let root = (0..64).fold(zero_hash::<F, H>(), |node, _| H::two_to_one(node, node));
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        let mut tree = SparseMerkleTree::<F, H>::new(1);
+        let empty_root = tree.root();
+        for (i, key) in keys().iter().enumerate() {
+            tree.insert(key, HashOut::from_partial(&[F::from_canonical_usize(i)]))
+                .unwrap();
+        }
+        let root = tree.root();
+
+        // the root does not depend on the order of insertions
+        let mut reversed = SparseMerkleTree::<F, H>::new(1);
+        for (i, key) in keys().iter().enumerate().rev() {
+            reversed
+                .insert(key, HashOut::from_partial(&[F::from_canonical_usize(i)]))
+                .unwrap();
+        }
+        assert_eq!(reversed.root(), root);
+
+        let key = keys()[0];
+        assert!(tree.insert(&key, HashOut::ZERO).is_err());
+        tree.update(&key, HashOut::from_partial(&[F::TWO; 4]))
+            .unwrap();
+        assert_ne!(tree.root(), root);
+        let absent = [F::from_canonical_u64(4)];
+        assert!(tree.update(&absent, HashOut::ZERO).is_err());
+        assert!(tree.delete(&absent).is_err());
+        assert!(tree.insert(&[F::ZERO, F::ZERO], HashOut::ZERO).is_err());
+        assert!(tree.prove(&[F::ZERO, F::ZERO]).is_err());
+
+        for key in keys() {
+            tree.delete(&key).unwrap();
+        }
+        assert_eq!(tree.root(), empty_root);
+    }
+
+    #[test]
+    fn proofs_recompute_the_root() {
+        let mut tree = SparseMerkleTree::<F, H>::new(2);
+        let present = (0..4).map(|_| [F::rand(), F::rand()]).collect::<Vec<_>>();
+        for key in present.iter() {
+            tree.insert(key, HashOut::rand()).unwrap();
+        }
+        for key in present.iter() {
+            let leaf = leaf_hash::<F, H>(key, tree.get(key).unwrap());
+            assert_eq!(fold(key, leaf, &tree.prove(key).unwrap()), tree.root());
+        }
+        let absent = [F::rand(), F::rand()];
+        let proof = tree.prove(&absent).unwrap();
+        assert_eq!(fold(&absent, zero_hash::<F, H>(), &proof), tree.root());
+        let leaf = leaf_hash::<F, H>(&absent, HashOut::ZERO);
+        assert_ne!(fold(&absent, leaf, &proof), tree.root());
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let mut tree = SparseMerkleTree::<F, H>::new(1);
+        let value = HashOut::from_partial(&[F::from_canonical_u64(7)]);
+        let key = [F::NEG_ONE];
+        tree.insert(&key, value)?;
+        let absent = [F::from_canonical_u64(5)];
+
+        let membership =
+            SmtCircuit::<F, C, H, D>::new(1, SmtStatement::Membership, ProofMode::Standard);
+        let proof = membership.prove(&tree, &key)?;
+        let expected = [&tree.root().elements[..], &key, &value.elements].concat();
+        assert_eq!(proof.public_inputs, expected);
+        membership.data.verify(proof)?;
+        assert!(membership.prove(&tree, &absent).is_err());
+
+        let non_membership =
+            SmtCircuit::<F, C, H, D>::new(1, SmtStatement::NonMembership, ProofMode::Standard);
+        let proof = non_membership.prove(&tree, &absent)?;
+        assert_eq!(
+            proof.public_inputs,
+            [&tree.root().elements[..], &absent].concat()
+        );
+        non_membership.data.verify(proof)?;
+        assert!(non_membership.prove(&tree, &key).is_err());
+        Ok(())
+    }
+}
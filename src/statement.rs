@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+use serde_json::Value;
+
+use crate::zk::ProofMode;
+
+// Arithmetic statements written as text and compiled into CircuitBuilder calls,
+// e.g. "a*b + c^3 - d = out". A statement is one or more equations separated by
+// ';' or newlines. Expressions use + - * ^ ( ), variables and integer constants;
+// exponents are integer constants. Every equation becomes lhs == rhs in the circuit.
+//
+// Statements and witnesses are JSON, so they can be written without touching Rust:
+//   { "statement": "a*b + c^3 - d = out", "public": ["out"] }
+//   { "a": 17, "b": 19, "c": 3, "d": 1, "out": 349 }
+// Public inputs are the public variables in the order they are listed, every other
+// variable is private. Values are numbers or decimal strings, and may be negative.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Var(String),
+    Const(u64),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, u64),
+}
+
+impl Expr {
+    fn collect_vars(&self, vars: &mut Vec<String>) {
+        match self {
+            Expr::Var(name) => {
+                if !vars.contains(name) {
+                    vars.push(name.clone());
+                }
+            }
+            Expr::Const(_) => {}
+            Expr::Neg(x) | Expr::Pow(x, _) => x.collect_vars(vars),
+            Expr::Add(x, y) | Expr::Sub(x, y) | Expr::Mul(x, y) => {
+                x.collect_vars(vars);
+                y.collect_vars(vars);
+            }
+        }
+    }
+
+    pub fn eval<F: RichField>(&self, values: &HashMap<String, F>) -> F {
+        match self {
+            Expr::Var(name) => values[name],
+            Expr::Const(c) => F::from_noncanonical_u64(*c),
+            Expr::Neg(x) => -x.eval(values),
+            Expr::Add(x, y) => x.eval(values) + y.eval(values),
+            Expr::Sub(x, y) => x.eval(values) - y.eval(values),
+            Expr::Mul(x, y) => x.eval(values) * y.eval(values),
+            Expr::Pow(x, e) => x.eval(values).exp_u64(*e),
+        }
+    }
+
+    pub fn build<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        targets: &HashMap<String, Target>,
+    ) -> Target {
+        match self {
+            Expr::Var(name) => targets[name],
+            Expr::Const(c) => builder.constant(F::from_noncanonical_u64(*c)),
+            Expr::Neg(x) => {
+                let x = x.build(builder, targets);
+                builder.neg(x)
+            }
+            Expr::Add(x, y) => {
+                let (x, y) = (x.build(builder, targets), y.build(builder, targets));
+                builder.add(x, y)
+            }
+            Expr::Sub(x, y) => {
+                let (x, y) = (x.build(builder, targets), y.build(builder, targets));
+                builder.sub(x, y)
+            }
+            Expr::Mul(x, y) => {
+                let (x, y) = (x.build(builder, targets), y.build(builder, targets));
+                builder.mul(x, y)
+            }
+            Expr::Pow(x, e) => {
+                let x = x.build(builder, targets);
+                builder.exp_u64(x, *e)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Num(u64),
+    Symbol(char),
+    Separator,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ';' || c == '\n' {
+            tokens.push(Token::Separator);
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits = chars[start..i].iter().collect::<String>();
+            let value = digits
+                .parse::<u64>()
+                .map_err(|_| anyhow!("constant {} does not fit in 64 bits", digits))?;
+            tokens.push(Token::Num(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*^()=".contains(c) {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        } else {
+            bail!("unexpected character '{}'", c);
+        }
+    }
+    Ok(tokens)
+}
+
+// recursive descent, from lowest to highest precedence:
+// equation := expr '=' expr
+// expr     := term (('+' | '-') term)*
+// term     := unary ('*' unary)*
+// unary    := '-' unary | power
+// power    := atom ('^' number)?
+// atom     := ident | number | '(' expr ')'
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        ensure!(
+            self.eat(symbol),
+            "expected '{}' but found {}",
+            symbol,
+            self.describe()
+        );
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            None => "end of input".to_string(),
+            Some(Token::Separator) => "end of equation".to_string(),
+            Some(Token::Ident(name)) => format!("'{}'", name),
+            Some(Token::Num(value)) => format!("'{}'", value),
+            Some(Token::Symbol(c)) => format!("'{}'", c),
+        }
+    }
+
+    fn equation(&mut self) -> Result<(Expr, Expr)> {
+        let lhs = self.expr()?;
+        self.expect('=')?;
+        let rhs = self.expr()?;
+        Ok((lhs, rhs))
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        loop {
+            if self.eat('+') {
+                lhs = Expr::Add(Box::new(lhs), Box::new(self.term()?));
+            } else if self.eat('-') {
+                lhs = Expr::Sub(Box::new(lhs), Box::new(self.term()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while self.eat('*') {
+            lhs = Expr::Mul(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr> {
+        let base = self.atom()?;
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        match self.peek().cloned() {
+            Some(Token::Num(e)) => {
+                self.pos += 1;
+                Ok(Expr::Pow(Box::new(base), e))
+            }
+            _ => bail!("exponent must be a constant, found {}", self.describe()),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(Expr::Var(name))
+            }
+            Some(Token::Num(value)) => {
+                self.pos += 1;
+                Ok(Expr::Const(value))
+            }
+            Some(Token::Symbol('(')) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            _ => bail!("expected a variable, constant or '(' but found {}", self.describe()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub equations: Vec<(Expr, Expr)>,
+    pub public: Vec<String>,
+    // public variables first, in the given order, then private ones by first appearance
+    pub variables: Vec<String>,
+}
+
+impl Statement {
+    pub fn parse(source: &str, public: &[&str]) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut equations = Vec::new();
+        for chunk in tokens.split(|token| *token == Token::Separator) {
+            if chunk.is_empty() {
+                continue;
+            }
+            let mut parser = Parser {
+                tokens: chunk.to_vec(),
+                pos: 0,
+            };
+            let equation = parser.equation()?;
+            ensure!(
+                parser.peek().is_none(),
+                "unexpected {} after equation {}",
+                parser.describe(),
+                equations.len() + 1
+            );
+            equations.push(equation);
+        }
+        ensure!(!equations.is_empty(), "the statement has no equations");
+
+        let mut appearing = Vec::new();
+        for (lhs, rhs) in equations.iter() {
+            lhs.collect_vars(&mut appearing);
+            rhs.collect_vars(&mut appearing);
+        }
+        let mut variables = Vec::new();
+        for &name in public {
+            ensure!(
+                appearing.iter().any(|v| v == name),
+                "public variable {} does not appear in the statement",
+                name
+            );
+            ensure!(!variables.iter().any(|v| v == name), "{} is listed twice", name);
+            variables.push(name.to_string());
+        }
+        for name in appearing {
+            if !variables.contains(&name) {
+                variables.push(name);
+            }
+        }
+
+        Ok(Statement {
+            equations,
+            public: public.iter().map(|name| name.to_string()).collect(),
+            variables,
+        })
+    }
+
+    // { "statement": "...", "public": ["..."] }
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        let source = value["statement"]
+            .as_str()
+            .ok_or_else(|| anyhow!("\"statement\" must be a string"))?;
+        let public = match &value["public"] {
+            Value::Null => Vec::new(),
+            Value::Array(names) => names
+                .iter()
+                .map(|name| {
+                    name.as_str()
+                        .ok_or_else(|| anyhow!("\"public\" must be a list of names"))
+                })
+                .collect::<Result<Vec<&str>>>()?,
+            _ => bail!("\"public\" must be a list of names"),
+        };
+        Self::parse(source, &public)
+    }
+
+    // reads a value for every variable from a JSON object
+    pub fn parse_witness<F: RichField>(&self, json: &str) -> Result<HashMap<String, F>> {
+        let value: Value = serde_json::from_str(json)?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| anyhow!("the witness must be a JSON object"))?;
+        for name in object.keys() {
+            ensure!(self.variables.contains(name), "unknown variable {}", name);
+        }
+        self.variables
+            .iter()
+            .map(|name| {
+                let value = object
+                    .get(name)
+                    .ok_or_else(|| anyhow!("no value for {}", name))?;
+                Ok((name.clone(), parse_value(value).map_err(|e| anyhow!("{}: {}", name, e))?))
+            })
+            .collect()
+    }
+
+    // checks every equation natively, so a wrong witness is an error rather than a
+    // failure during proving
+    pub fn check<F: RichField>(&self, values: &HashMap<String, F>) -> Result<()> {
+        for (i, (lhs, rhs)) in self.equations.iter().enumerate() {
+            ensure!(
+                lhs.eval(values) == rhs.eval(values),
+                "equation {} does not hold for the witness",
+                i + 1
+            );
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<F: RichField>(value: &Value) -> Result<F> {
+    let text = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.trim().to_string(),
+        _ => bail!("values must be integers or decimal strings"),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.as_str()),
+    };
+    let magnitude = digits
+        .parse::<u64>()
+        .map_err(|_| anyhow!("{} is not a 64-bit integer", text))?;
+    let x = F::from_noncanonical_u64(magnitude);
+    Ok(if negative { -x } else { x })
+}
+
+pub struct StatementTargets {
+    pub variables: HashMap<String, Target>,
+}
+
+pub fn statement_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    statement: &Statement,
+) -> StatementTargets {
+    let variables = statement
+        .variables
+        .iter()
+        .map(|name| (name.clone(), builder.add_virtual_target()))
+        .collect::<HashMap<String, Target>>();
+    for name in statement.public.iter() {
+        builder.register_public_input(variables[name]);
+    }
+    for (lhs, rhs) in statement.equations.iter() {
+        let lhs = lhs.build(builder, &variables);
+        let rhs = rhs.build(builder, &variables);
+        builder.connect(lhs, rhs);
+    }
+    StatementTargets { variables }
+}
+
+pub struct StatementCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub statement: Statement,
+    pub targets: StatementTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    StatementCircuit<F, C, D>
+{
+    pub fn new(statement: Statement, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = statement_circuit(&mut builder, &statement);
+        let data = builder.build::<C>();
+        StatementCircuit {
+            statement,
+            targets,
+            data,
+        }
+    }
+
+    pub fn prove(&self, witness_json: &str) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let values = self.statement.parse_witness::<F>(witness_json)?;
+        self.statement.check(&values)?;
+        let mut pw = PartialWitness::new();
+        for (name, &target) in self.targets.variables.iter() {
+            pw.set_target(target, values[name]);
+        }
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Var(name.to_string()))
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize("x_1*(b+ 12)\n;").unwrap(),
+            vec![
+                Token::Ident("x_1".to_string()),
+                Token::Symbol('*'),
+                Token::Symbol('('),
+                Token::Ident("b".to_string()),
+                Token::Symbol('+'),
+                Token::Num(12),
+                Token::Symbol(')'),
+                Token::Separator,
+                Token::Separator,
+            ]
+        );
+        assert!(tokenize("a / b").is_err());
+        assert!(tokenize("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        // unary minus binds looser than ^, and - associates to the left
+        let statement = Statement::parse("-a^2 + b*c - 1 - d = out", &["out"]).unwrap();
+        let lhs = Expr::Sub(
+            Box::new(Expr::Sub(
+                Box::new(Expr::Add(
+                    Box::new(Expr::Neg(Box::new(Expr::Pow(var("a"), 2)))),
+                    Box::new(Expr::Mul(var("b"), var("c"))),
+                )),
+                Box::new(Expr::Const(1)),
+            )),
+            var("d"),
+        );
+        assert_eq!(statement.equations, vec![(lhs, *var("out"))]);
+        assert_eq!(statement.variables, ["out", "a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for source in ["", "a + = b", "a = b c", "a ^ b = c", "(a = b", "a + b"] {
+            assert!(
+                Statement::parse(source, &[]).is_err(),
+                "{:?} parsed",
+                source
+            );
+        }
+        assert!(Statement::parse("a = b", &["c"]).is_err());
+        assert!(Statement::parse("a = b", &["a", "a"]).is_err());
+        assert!(Statement::from_json(r#"{ "statement": "a = b", "public": "a" }"#).is_err());
+    }
+
+    #[test]
+    fn witnesses() {
+        let statement = Statement::parse("a * b = c; a - b = d", &["c"]).unwrap();
+        let values = statement
+            .parse_witness::<F>(r#"{ "a": 17, "b": "19", "c": 323, "d": -2 }"#)
+            .unwrap();
+        assert_eq!(values["d"], -F::TWO);
+        statement.check(&values).unwrap();
+
+        let wrong = statement
+            .parse_witness::<F>(r#"{ "a": 17, "b": 19, "c": 324, "d": -2 }"#)
+            .unwrap();
+        assert!(statement.check(&wrong).is_err());
+        assert!(statement
+            .parse_witness::<F>(r#"{ "a": 1, "b": 1, "c": 1 }"#)
+            .is_err());
+        assert!(statement
+            .parse_witness::<F>(r#"{ "a": 1, "b": 1, "c": 1, "d": 0, "e": 0 }"#)
+            .is_err());
+        assert!(statement
+            .parse_witness::<F>(r#"{ "a": 1.5, "b": 1, "c": 1, "d": 0 }"#)
+            .is_err());
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let statement = Statement::from_json(
+            r#"{ "statement": "a*b + c^3 - d = out; a - b = diff", "public": ["out", "diff"] }"#,
+        )?;
+        let circuit = StatementCircuit::<F, C, D>::new(statement, ProofMode::Standard);
+        let proof =
+            circuit.prove(r#"{ "a": 17, "b": 19, "c": 3, "d": 1, "out": 349, "diff": -2 }"#)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(349), -F::TWO]);
+        circuit.data.verify(proof)?;
+
+        assert!(circuit
+            .prove(r#"{ "a": 17, "b": 19, "c": 3, "d": 1, "out": 350, "diff": -2 }"#)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{extension::Extendable, types::Sample},
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_tree::MerkleTree,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{AlgebraicHasher, GenericConfig},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::merkle::{
+    add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness, MerkleProofTargets,
+};
+use crate::zk::ProofMode;
+
+// Tornado-style mixer, REF: https://github.com/tornadocash/tornado-core/blob/master/circuits/withdraw.circom
+// A deposit inserts commitment = H(nullifier, secret) into the deposit tree.
+// A withdrawal proves knowledge of a note whose commitment is in the tree and
+// reveals H(nullifier), so the same note cannot be withdrawn twice.
+//
+// Public inputs are laid out as follows:
+// 0..4 is the root, 4..8 the nullifier hash, 8..12 the recipient, 12 the fee.
+
+#[derive(Clone, Copy, Debug)]
+pub struct Note<F: RichField> {
+    pub nullifier: HashOut<F>,
+    pub secret: HashOut<F>,
+}
+
+impl<F: RichField> Note<F> {
+    pub fn new() -> Self {
+        Note {
+            nullifier: HashOut::rand(),
+            secret: HashOut::rand(),
+        }
+    }
+
+    pub fn commitment<H: AlgebraicHasher<F>>(&self) -> HashOut<F> {
+        let inputs = [self.nullifier.elements, self.secret.elements].concat();
+        H::hash_no_pad(&inputs)
+    }
+
+    pub fn nullifier_hash<H: AlgebraicHasher<F>>(&self) -> HashOut<F> {
+        H::hash_no_pad(&self.nullifier.elements)
+    }
+}
+
+impl<F: RichField> Default for Note<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Fixed height tree of deposits, unused leaves are zero.
+pub struct DepositTree<F: RichField, H: AlgebraicHasher<F>> {
+    pub height: usize,
+    pub commitments: Vec<HashOut<F>>,
+    _hasher: PhantomData<H>,
+}
+
+impl<F: RichField, H: AlgebraicHasher<F>> DepositTree<F, H> {
+    pub fn new(height: usize) -> Self {
+        DepositTree {
+            height,
+            commitments: Vec::new(),
+            _hasher: PhantomData,
+        }
+    }
+
+    // inserts the commitment and returns its leaf index
+    pub fn deposit(&mut self, commitment: HashOut<F>) -> Result<usize> {
+        ensure!(self.commitments.len() < 1 << self.height, "deposit tree is full");
+        self.commitments.push(commitment);
+        Ok(self.commitments.len() - 1)
+    }
+
+    pub fn tree(&self) -> MerkleTree<F, H> {
+        let mut leaves = self
+            .commitments
+            .iter()
+            .map(|commitment| commitment.elements.to_vec())
+            .collect::<Vec<Vec<F>>>();
+        leaves.resize(1 << self.height, vec![F::ZERO; 4]);
+        MerkleTree::new(leaves, 0)
+    }
+
+    pub fn root(&self) -> HashOut<F> {
+        self.tree().cap.0[0]
+    }
+}
+
+pub struct WithdrawTargets {
+    pub nullifier: HashOutTarget,
+    pub secret: HashOutTarget,
+    pub merkle_proof: MerkleProofTargets,
+    pub root: HashOutTarget,
+    pub recipient: HashOutTarget,
+    pub fee: Target,
+}
+
+pub fn withdraw_circuit<H: AlgebraicHasher<F>, F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> WithdrawTargets {
+    let nullifier = builder.add_virtual_hash();
+    let secret = builder.add_virtual_hash();
+    let merkle_proof = add_virtual_merkle_proof(builder, height);
+    let root = builder.add_virtual_hash();
+    let recipient = builder.add_virtual_hash();
+    let fee = builder.add_virtual_target();
+
+    let inputs = [nullifier.elements, secret.elements].concat();
+    let commitment = builder.hash_n_to_hash_no_pad::<H>(inputs);
+    let computed_root = merkle_root_circuit::<H, F, D>(builder, commitment, &merkle_proof);
+    builder.connect_hashes(computed_root, root);
+
+    let nullifier_hash = builder.hash_n_to_hash_no_pad::<H>(nullifier.elements.to_vec());
+
+    // recipient and fee are not used in any constraint, registering them as
+    // public inputs is enough to bind them to the proof
+    builder.register_public_inputs(&root.elements);
+    builder.register_public_inputs(&nullifier_hash.elements);
+    builder.register_public_inputs(&recipient.elements);
+    builder.register_public_input(fee);
+
+    WithdrawTargets {
+        nullifier,
+        secret,
+        merkle_proof,
+        root,
+        recipient,
+        fee,
+    }
+}
+
+pub struct WithdrawCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    H: AlgebraicHasher<F>,
+    const D: usize,
+> {
+    pub targets: WithdrawTargets,
+    pub data: CircuitData<F, C, D>,
+    _hasher: PhantomData<H>,
+}
+
+impl<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        H: AlgebraicHasher<F>,
+        const D: usize,
+    > WithdrawCircuit<F, C, H, D>
+{
+    pub fn new(height: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = withdraw_circuit::<H, F, D>(&mut builder, height);
+        let data = builder.build::<C>();
+        WithdrawCircuit {
+            targets,
+            data,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        deposits: &DepositTree<F, H>,
+        note: &Note<F>,
+        leaf_index: usize,
+        recipient: HashOut<F>,
+        fee: F,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            leaf_index < deposits.commitments.len(),
+            "no deposit at this leaf index"
+        );
+        let tree = deposits.tree();
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(self.targets.nullifier, note.nullifier);
+        pw.set_hash_target(self.targets.secret, note.secret);
+        set_merkle_proof_witness(
+            &mut pw,
+            &self.targets.merkle_proof,
+            leaf_index,
+            &tree.prove(leaf_index),
+        );
+        pw.set_hash_target(self.targets.root, tree.cap.0[0]);
+        pw.set_hash_target(self.targets.recipient, recipient);
+        pw.set_target(self.targets.fee, fee);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        hash::poseidon::PoseidonHash,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type H = PoseidonHash;
+
+    const HEIGHT: usize = 2;
+
+    #[test]
+    fn deposits() -> Result<()> {
+        let mut deposits = DepositTree::<F, H>::new(HEIGHT);
+        let empty = deposits.root();
+        for i in 0..1 << HEIGHT {
+            assert_eq!(deposits.deposit(Note::new().commitment::<H>())?, i);
+        }
+        assert_ne!(deposits.root(), empty);
+        assert!(deposits.deposit(Note::new().commitment::<H>()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let mut deposits = DepositTree::<F, H>::new(HEIGHT);
+        let notes = (0..3).map(|_| Note::<F>::new()).collect::<Vec<Note<F>>>();
+        for note in notes.iter() {
+            deposits.deposit(note.commitment::<H>())?;
+        }
+        let circuit = WithdrawCircuit::<F, C, H, D>::new(HEIGHT, ProofMode::Standard);
+        let recipient = HashOut::from_partial(&[F::ONE]);
+        let fee = F::from_canonical_u64(10);
+
+        let proof = circuit.prove(&deposits, &notes[1], 1, recipient, fee)?;
+        let expected = [
+            deposits.root().elements.to_vec(),
+            notes[1].nullifier_hash::<H>().elements.to_vec(),
+            recipient.elements.to_vec(),
+            vec![fee],
+        ]
+        .concat();
+        assert_eq!(proof.public_inputs, expected);
+        circuit.data.verify(proof.clone())?;
+
+        // the nullifier hash does not depend on the recipient, so a second withdrawal
+        // of the note is caught by the set of spent ones
+        let mut spent = HashSet::new();
+        assert!(spent.insert(proof.public_inputs[4..8].to_vec()));
+        let other = HashOut::from_partial(&[F::TWO]);
+        let again = circuit.prove(&deposits, &notes[1], 1, other, fee)?;
+        circuit.data.verify(again.clone())?;
+        assert!(!spent.insert(again.public_inputs[4..8].to_vec()));
+
+        // no deposit at leaf 3, and a note at the wrong leaf misses the root
+        assert!(circuit
+            .prove(&deposits, &notes[1], 3, recipient, fee)
+            .is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            circuit.prove(&deposits, &notes[1], 2, recipient, fee)
+        }));
+        assert!(!matches!(result, Ok(Ok(_))));
+        Ok(())
+    }
+}
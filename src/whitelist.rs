@@ -0,0 +1,305 @@
+use anyhow::{anyhow, ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_tree::MerkleTree,
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, CommonCircuitData, VerifierCircuitTarget},
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+        proof::ProofWithPublicInputsTarget,
+    },
+};
+
+use crate::merkle::{
+    add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness, MerkleProofTargets,
+};
+use crate::recursion::{merged_tree_info, split_tree_info, ProofTuple};
+use crate::zk::ProofMode;
+
+// A universal aggregator: one circuit that verifies a proof of any circuit whose verifier
+// data digest is a leaf of a public Merkle root of allowed circuits. Unlike a merge tree,
+// the inner verifier data is a witness, so one deployed verifier key accepts a growing
+// set of circuits; whoever governs the set publishes a new root, up to 2^height circuits.
+//
+// plonky2 can only verify a proof in a circuit against fixed common data, so every allowed
+// circuit has the shape the aggregator is built for: the same config, gate set, degree
+// and number of public inputs, e.g. circuits built from the same gadgets.
+// The digest plonky2 puts in `VerifierOnlyCircuitData` hashes the constants and sigmas cap
+// with the (default, empty) domain separator and the degree. The verifier gadget does not
+// check that the two agree, so the circuit recomputes the digest from the cap before
+// looking it up.
+//
+// The aggregator is built for proofs of one depth: leaf proofs, or merged proofs whose
+// public inputs end with their tree info. Its own proof is one level up, so it can be
+// merged or shrunk like any other `ProofTuple`.
+//
+// Public inputs are the whitelist root, the inner proof's statement, then the depth and
+// leaf count of the tree.
+
+pub struct Whitelist<F: RichField> {
+    pub tree: MerkleTree<F, PoseidonHash>,
+}
+
+// native helpers
+
+impl<F: RichField> Whitelist<F> {
+    /// A tree of height `height` with the circuit digests as its first leaves, the others
+    /// zero.
+    pub fn new(digests: &[HashOut<F>], height: usize) -> Result<Self> {
+        ensure!(
+            digests.len() <= 1 << height,
+            "more circuits than leaves in the whitelist"
+        );
+        let mut leaves = digests
+            .iter()
+            .map(|digest| digest.elements.to_vec())
+            .collect::<Vec<Vec<F>>>();
+        leaves.resize(1 << height, vec![F::ZERO; 4]);
+        Ok(Whitelist {
+            tree: MerkleTree::new(leaves, 0),
+        })
+    }
+
+    pub fn root(&self) -> HashOut<F> {
+        self.tree.cap.0[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.tree.leaves.len().trailing_zeros() as usize
+    }
+
+    pub fn index_of(&self, digest: HashOut<F>) -> Option<usize> {
+        self.tree
+            .leaves
+            .iter()
+            .position(|leaf| leaf[..] == digest.elements[..])
+    }
+}
+
+// circuit
+
+pub struct WhitelistTargets<const D: usize> {
+    pub proof: ProofWithPublicInputsTarget<D>,
+    pub verifier_data: VerifierCircuitTarget,
+    pub merkle_proof: MerkleProofTargets,
+    pub root: HashOutTarget,
+}
+
+/// Verifies a proof of shape `inner` and depth `depth` made by any circuit in a whitelist
+/// of height `height`.
+pub fn whitelist_circuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    inner: &CommonCircuitData<F, D>,
+    depth: u32,
+    height: usize,
+) -> WhitelistTargets<D>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let proof = builder.add_virtual_proof_with_pis(inner);
+    let verifier_data = builder.add_virtual_verifier_data(inner.config.fri_config.cap_height);
+    builder.verify_proof::<C>(&proof, &verifier_data, inner);
+
+    // the digest must be the one of this cap, as in `CircuitBuilder::build`
+    let domain_separator_digest = C::Hasher::hash_pad(&[]);
+    let mut inputs = verifier_data
+        .constants_sigmas_cap
+        .0
+        .iter()
+        .flat_map(|h| h.elements)
+        .collect::<Vec<Target>>();
+    for x in domain_separator_digest.elements {
+        inputs.push(builder.constant(x));
+    }
+    inputs.push(builder.constant(F::from_canonical_usize(inner.degree_bits())));
+    let digest = builder.hash_n_to_hash_no_pad::<C::Hasher>(inputs);
+    builder.connect_hashes(digest, verifier_data.circuit_digest);
+
+    let merkle_proof = add_virtual_merkle_proof(builder, height);
+    let root = merkle_root_circuit::<PoseidonHash, F, D>(
+        builder,
+        verifier_data.circuit_digest,
+        &merkle_proof,
+    );
+
+    let (statement, info) = split_tree_info(builder, depth, &proof);
+    let tree_info = merged_tree_info(builder, &[depth], &[info]);
+    builder.register_public_inputs(&root.elements);
+    builder.register_public_inputs(&statement);
+    builder.register_public_inputs(&tree_info);
+
+    WhitelistTargets {
+        proof,
+        verifier_data,
+        merkle_proof,
+        root,
+    }
+}
+
+pub struct WhitelistCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub inner: CommonCircuitData<F, D>,
+    pub depth: u32,
+    pub targets: WhitelistTargets<D>,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    WhitelistCircuit<F, C, D>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn new(
+        inner: &CommonCircuitData<F, D>,
+        depth: u32,
+        height: usize,
+        proof_mode: ProofMode,
+    ) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = whitelist_circuit::<F, C, D>(&mut builder, inner, depth, height);
+        let data = builder.build::<C>();
+        WhitelistCircuit {
+            inner: inner.clone(),
+            depth,
+            targets,
+            data,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        child: &ProofTuple<F, C, D>,
+        whitelist: &Whitelist<F>,
+    ) -> Result<ProofTuple<F, C, D>> {
+        ensure!(
+            child.cd == self.inner,
+            "proof has another shape than the whitelisted circuits"
+        );
+        ensure!(
+            child.depth == self.depth,
+            "proof has another depth than the whitelisted circuits"
+        );
+        ensure!(
+            whitelist.height() == self.targets.merkle_proof.siblings.len(),
+            "whitelist is of another height"
+        );
+        let leaf_index = whitelist
+            .index_of(child.vd.circuit_digest)
+            .ok_or_else(|| anyhow!("circuit is not whitelisted"))?;
+        // an invalid proof would only fail deep inside witness generation
+        ensure!(
+            child.verifier_data().verify(child.proof.clone()).is_ok(),
+            "invalid proof"
+        );
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target::<C, D>(&self.targets.proof, &child.proof);
+        pw.set_verifier_data_target(&self.targets.verifier_data, &child.vd);
+        set_merkle_proof_witness(
+            &mut pw,
+            &self.targets.merkle_proof,
+            leaf_index,
+            &whitelist.tree.prove(leaf_index),
+        );
+        let proof = self.data.prove(pw)?;
+        Ok(ProofTuple {
+            proof,
+            vd: self.data.verifier_only.clone(),
+            cd: self.data.common.clone(),
+            depth: self.depth + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    // circuits of one shape whose digests differ by a constant: output = k * input
+    fn scale_circuit(k: u64) -> (Target, CircuitData<F, C, D>) {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let input = builder.add_virtual_target();
+        let output = builder.mul_const(F::from_canonical_u64(k), input);
+        builder.register_public_input(input);
+        builder.register_public_input(output);
+        (input, builder.build::<C>())
+    }
+
+    fn scale_proof(k: u64, x: u64) -> Result<ProofTuple<F, C, D>> {
+        let (input, data) = scale_circuit(k);
+        let mut pw = PartialWitness::new();
+        pw.set_target(input, F::from_canonical_u64(x));
+        Ok(ProofTuple::leaf(data.prove(pw)?, &data))
+    }
+
+    #[test]
+    fn whitelist_round_trip() -> Result<()> {
+        let five = scale_proof(5, 3)?;
+        let seven = scale_proof(7, 3)?;
+        assert!(five.cd == seven.cd);
+        assert_ne!(five.vd.circuit_digest, seven.vd.circuit_digest);
+
+        let whitelist = Whitelist::new(&[HashOut::ZERO, five.vd.circuit_digest], 2)?;
+        assert_eq!(whitelist.index_of(five.vd.circuit_digest), Some(1));
+        let circuit = WhitelistCircuit::<F, C, D>::new(&five.cd, 0, 2, ProofMode::Standard);
+        let proof = circuit.prove(&five, &whitelist)?;
+        let expected = [
+            whitelist.root().elements.to_vec(),
+            five.proof.public_inputs.clone(),
+            vec![F::ONE, F::ONE],
+        ]
+        .concat();
+        assert_eq!(proof.proof.public_inputs, expected);
+        assert_eq!((proof.depth, proof.leaves()), (1, 1));
+        proof.verifier_data().verify(proof.proof.clone())?;
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_digest_not_whitelisted() -> Result<()> {
+        let five = scale_proof(5, 3)?;
+        let seven = scale_proof(7, 3)?;
+        let whitelist = Whitelist::new(&[five.vd.circuit_digest], 1)?;
+        let circuit = WhitelistCircuit::<F, C, D>::new(&five.cd, 0, 1, ProofMode::Standard);
+        assert!(circuit.prove(&seven, &whitelist).is_err());
+
+        // with the path of the whitelisted leaf, the circuit reaches another root
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target::<C, D>(&circuit.targets.proof, &seven.proof);
+        pw.set_verifier_data_target(&circuit.targets.verifier_data, &seven.vd);
+        set_merkle_proof_witness(
+            &mut pw,
+            &circuit.targets.merkle_proof,
+            0,
+            &whitelist.tree.prove(0),
+        );
+        let proof = circuit.data.prove(pw)?;
+        assert_ne!(proof.public_inputs[..4], whitelist.root().elements);
+
+        assert!(Whitelist::<F>::new(&[HashOut::ZERO; 3], 1).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::{bits::split_canonical, merkle::select_hash, zk::ProofMode};
+
+// Winternitz one-time signatures with Poseidon and w = 16.
+// A message is a digest of 4 elements, read as 64 base-16 digits of 4 bits, least
+// significant first, followed by 3 digits of the checksum sum(15 - d_i).
+// Chain i starts at a secret and steps x -> H(x, i, j) for j = 0, .., 14. A signature
+// reveals step d_i of every chain, and the public key is the hash of all the chain ends.
+// Pushing a signature further along a chain raises its digit but lowers the checksum,
+// so nothing can be signed without the secret key.
+//
+// Public inputs are the message then the public key.
+
+pub const LOG_W: usize = 4;
+pub const W: usize = 1 << LOG_W;
+pub const MESSAGE_DIGITS: usize = 4 * 64 / LOG_W;
+// the checksum is at most 64 * 15 = 960 < 16^3
+pub const CHECKSUM_DIGITS: usize = 3;
+pub const NUM_CHAINS: usize = MESSAGE_DIGITS + CHECKSUM_DIGITS;
+
+const MAX_CHECKSUM: usize = MESSAGE_DIGITS * (W - 1);
+
+// last input of the key derivation H(seed, index, i, KEY_TAG). `chain_step` hashes 6
+// elements, and with no padding those hash like the same 6 followed by a zero, so the
+// nonzero tag keeps a secret from ever being a chain value.
+const KEY_TAG: u64 = 0x6b6579;
+
+#[derive(Clone, Debug)]
+pub struct WotsSecretKey<F: RichField>(pub Vec<HashOut<F>>);
+
+#[derive(Clone, Debug)]
+pub struct WotsSignature<F: RichField>(pub Vec<HashOut<F>>);
+
+// native helpers
+
+fn chain_step<F: RichField>(x: HashOut<F>, chain: usize, step: usize) -> HashOut<F> {
+    let inputs = [
+        x.elements.to_vec(),
+        vec![
+            F::from_canonical_usize(chain),
+            F::from_canonical_usize(step),
+        ],
+    ]
+    .concat();
+    PoseidonHash::hash_no_pad(&inputs)
+}
+
+// steps from..to of the chain
+fn chain<F: RichField>(mut x: HashOut<F>, chain: usize, from: usize, to: usize) -> HashOut<F> {
+    for step in from..to {
+        x = chain_step(x, chain, step);
+    }
+    x
+}
+
+/// The message digits followed by the checksum digits.
+pub fn message_digits<F: RichField>(msg: HashOut<F>) -> Vec<usize> {
+    let mut digits = msg
+        .elements
+        .iter()
+        .flat_map(|x| {
+            let x = x.to_canonical_u64();
+            (0..64 / LOG_W).map(move |k| ((x >> (LOG_W * k)) as usize) & (W - 1))
+        })
+        .collect::<Vec<usize>>();
+    let checksum = MAX_CHECKSUM - digits.iter().sum::<usize>();
+    digits.extend((0..CHECKSUM_DIGITS).map(|k| (checksum >> (LOG_W * k)) & (W - 1)));
+    digits
+}
+
+impl<F: RichField> WotsSecretKey<F> {
+    /// The key derived from a secret seed and an index, so that a many-time scheme
+    /// only stores the seed.
+    pub fn from_seed(seed: HashOut<F>, index: usize) -> Self {
+        let chains = (0..NUM_CHAINS)
+            .map(|i| {
+                let inputs = [
+                    seed.elements.to_vec(),
+                    vec![
+                        F::from_canonical_usize(index),
+                        F::from_canonical_usize(i),
+                        F::from_canonical_u64(KEY_TAG),
+                    ],
+                ]
+                .concat();
+                PoseidonHash::hash_no_pad(&inputs)
+            })
+            .collect();
+        WotsSecretKey(chains)
+    }
+
+    pub fn to_public(&self) -> HashOut<F> {
+        let ends = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| chain(x, i, 0, W - 1))
+            .collect::<Vec<HashOut<F>>>();
+        public_key_from_ends(&ends)
+    }
+
+    pub fn sign(&self, msg: HashOut<F>) -> WotsSignature<F> {
+        let chains = message_digits(msg)
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| chain(self.0[i], i, 0, d))
+            .collect();
+        WotsSignature(chains)
+    }
+}
+
+fn public_key_from_ends<F: RichField>(ends: &[HashOut<F>]) -> HashOut<F> {
+    let inputs = ends.iter().flat_map(|x| x.elements).collect::<Vec<F>>();
+    PoseidonHash::hash_no_pad(&inputs)
+}
+
+/// Completes every chain of the signature, giving the public key it was made with.
+pub fn public_key_from_signature<F: RichField>(
+    msg: HashOut<F>,
+    sig: &WotsSignature<F>,
+) -> HashOut<F> {
+    let ends = message_digits(msg)
+        .into_iter()
+        .enumerate()
+        .map(|(i, d)| chain(sig.0[i], i, d, W - 1))
+        .collect::<Vec<HashOut<F>>>();
+    public_key_from_ends(&ends)
+}
+
+pub fn verify<F: RichField>(msg: HashOut<F>, sig: &WotsSignature<F>, pk: HashOut<F>) -> bool {
+    sig.0.len() == NUM_CHAINS && public_key_from_signature(msg, sig) == pk
+}
+
+// circuit
+
+/// The digits of `message_digits`, 4-bit targets.
+pub fn message_digits_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg: HashOutTarget,
+) -> Vec<Target> {
+    let mut digits = msg
+        .elements
+        .iter()
+        .flat_map(|&x| split_canonical(builder, x))
+        .collect::<Vec<_>>()
+        .chunks(LOG_W)
+        .map(|bits| builder.le_sum(bits.iter()))
+        .collect::<Vec<Target>>();
+
+    let sum = builder.add_many(digits.iter());
+    let max = builder.constant(F::from_canonical_usize(MAX_CHECKSUM));
+    let checksum = builder.sub(max, sum);
+    let checksum_bits = builder.split_le(checksum, LOG_W * CHECKSUM_DIGITS);
+    digits.extend(
+        checksum_bits
+            .chunks(LOG_W)
+            .map(|bits| builder.le_sum(bits.iter())),
+    );
+    digits
+}
+
+/// The public key that `sig` completes to, like `public_key_from_signature`.
+pub fn public_key_from_signature_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg: HashOutTarget,
+    sig: &[HashOutTarget],
+) -> HashOutTarget {
+    assert_eq!(sig.len(), NUM_CHAINS);
+    let digits = message_digits_circuit(builder, msg);
+
+    let mut ends = Vec::with_capacity(NUM_CHAINS);
+    for (i, (&d, &start)) in digits.iter().zip(sig.iter()).enumerate() {
+        let chain = builder.constant(F::from_canonical_usize(i));
+        let mut x = start;
+        // step j is taken once j >= d, exactly one of the j equals d unless d = 15
+        let mut reached = builder._false();
+        for j in 0..W - 1 {
+            let step = builder.constant(F::from_canonical_usize(j));
+            let at_start = builder.is_equal(d, step);
+            reached = builder.or(reached, at_start);
+            let inputs = [x.elements.to_vec(), vec![chain, step]].concat();
+            let next = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+            x = select_hash(builder, reached, next, x);
+        }
+        ends.push(x);
+    }
+
+    let inputs = ends
+        .iter()
+        .flat_map(|x| x.elements)
+        .collect::<Vec<Target>>();
+    builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs)
+}
+
+pub struct WotsTargets {
+    pub msg: HashOutTarget,
+    pub pk: HashOutTarget,
+    pub sig: Vec<HashOutTarget>,
+}
+
+pub fn wots_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> WotsTargets {
+    let msg = builder.add_virtual_hash();
+    let sig = builder.add_virtual_hashes(NUM_CHAINS);
+    let pk = public_key_from_signature_circuit(builder, msg, &sig);
+
+    builder.register_public_inputs(&msg.elements);
+    builder.register_public_inputs(&pk.elements);
+
+    WotsTargets { msg, pk, sig }
+}
+
+pub fn set_wots_signature<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &[HashOutTarget],
+    sig: &WotsSignature<F>,
+) {
+    for (&target, &x) in targets.iter().zip(sig.0.iter()) {
+        pw.set_hash_target(target, x);
+    }
+}
+
+pub struct WotsCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub targets: WotsTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    WotsCircuit<F, C, D>
+{
+    pub fn new(proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = wots_circuit(&mut builder);
+        let data = builder.build::<C>();
+        WotsCircuit { targets, data }
+    }
+
+    pub fn prove(
+        &self,
+        msg: HashOut<F>,
+        sig: &WotsSignature<F>,
+        pk: HashOut<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(verify(msg, sig, pk), "invalid signature");
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(self.targets.msg, msg);
+        set_wots_signature(&mut pw, &self.targets.sig, sig);
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    // small elements, so that raising a digit keeps them canonical
+    fn message() -> HashOut<F> {
+        HashOut {
+            elements: [0x1234, 0x5678, 0x9abc, 0xdef0].map(F::from_canonical_u64),
+        }
+    }
+
+    #[test]
+    fn digits() {
+        let digits = message_digits(message());
+        assert_eq!(digits.len(), NUM_CHAINS);
+        assert_eq!(digits[..5], [4, 3, 2, 1, 0]);
+        let checksum = MAX_CHECKSUM - digits[..MESSAGE_DIGITS].iter().sum::<usize>();
+        assert_eq!(digits[MESSAGE_DIGITS], checksum & (W - 1));
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let sk = WotsSecretKey::from_seed(HashOut::from_partial(&[F::ONE]), 0);
+        let pk = sk.to_public();
+        let sig = sk.sign(message());
+        assert!(verify(message(), &sig, pk));
+        assert!(!verify(HashOut::from_partial(&[F::ONE]), &sig, pk));
+        assert_ne!(
+            WotsSecretKey::from_seed(HashOut::from_partial(&[F::ONE]), 1).to_public(),
+            pk
+        );
+    }
+
+    #[test]
+    fn rejects_pushed_digit() {
+        let sk = WotsSecretKey::from_seed(HashOut::from_partial(&[F::ONE]), 0);
+        let pk = sk.to_public();
+        let msg = message();
+        let mut sig = sk.sign(msg);
+
+        // anyone can take chain 0 from digit 4 to digit 5, signing msg + 1 there
+        sig.0[0] = chain_step(sig.0[0], 0, 4);
+        let mut raised = msg;
+        raised.elements[0] += F::ONE;
+        assert_eq!(message_digits(raised)[0], 5);
+        // but the checksum went down, and its chains cannot be walked back
+        assert!(!verify(raised, &sig, pk));
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let sk = WotsSecretKey::from_seed(HashOut::from_partial(&[F::ONE]), 0);
+        let pk = sk.to_public();
+        let msg = message();
+        let sig = sk.sign(msg);
+
+        let circuit = WotsCircuit::<F, C, D>::new(ProofMode::Standard);
+        let proof = circuit.prove(msg, &sig, pk)?;
+        assert_eq!(proof.public_inputs, [msg.elements, pk.elements].concat());
+        circuit.data.verify(proof)?;
+
+        // with the pushed digit the circuit completes the chains to another key
+        let mut forged = sig.clone();
+        forged.0[0] = chain_step(forged.0[0], 0, 4);
+        let mut raised = msg;
+        raised.elements[0] += F::ONE;
+        assert!(circuit.prove(raised, &forged, pk).is_err());
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(circuit.targets.msg, raised);
+        set_wots_signature(&mut pw, &circuit.targets.sig, &forged);
+        let proof = circuit.data.prove(pw)?;
+        assert_ne!(proof.public_inputs[4..8], pk.elements);
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_proofs::{verify_merkle_proof, MerkleProof},
+        merkle_tree::MerkleTree,
+        poseidon::PoseidonHash,
+    },
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitData,
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+};
+
+use crate::{
+    merkle::{
+        add_virtual_merkle_proof, merkle_root_circuit, set_merkle_proof_witness, MerkleProofTargets,
+    },
+    winternitz::{
+        public_key_from_signature, public_key_from_signature_circuit, set_wots_signature,
+        WotsSecretKey, WotsSignature, NUM_CHAINS,
+    },
+    zk::ProofMode,
+};
+
+// XMSS-style many-time signatures: the Winternitz public keys for leaf indices
+// 0..2^height, each derived from one secret seed, are the leaves of a Poseidon Merkle tree
+// whose root is the public key. A signature is a leaf index, the one-time signature made
+// with that leaf's key and the leaf's Merkle path.
+// The signer is stateful: it moves to the next leaf after every signature, since signing
+// two messages with one Winternitz key gives away enough to forge others.
+//
+// Public inputs are the message then the root. The leaf index stays private.
+
+#[derive(Clone, Debug)]
+pub struct XmssSignature<F: RichField> {
+    pub leaf_index: usize,
+    pub wots: WotsSignature<F>,
+    pub merkle_proof: MerkleProof<F, PoseidonHash>,
+}
+
+pub struct XmssSigner<F: RichField> {
+    seed: HashOut<F>,
+    pub tree: MerkleTree<F, PoseidonHash>,
+    next_leaf: usize,
+}
+
+// native helpers
+
+impl<F: RichField> XmssSigner<F> {
+    /// Generates all 2^height one-time keys from `seed`, which must stay secret.
+    pub fn new(seed: HashOut<F>, height: usize) -> Self {
+        let leaves = (0..1 << height)
+            .map(|i| {
+                WotsSecretKey::from_seed(seed, i)
+                    .to_public()
+                    .elements
+                    .to_vec()
+            })
+            .collect::<Vec<Vec<F>>>();
+        let tree = MerkleTree::new(leaves, 0);
+        XmssSigner {
+            seed,
+            tree,
+            next_leaf: 0,
+        }
+    }
+
+    pub fn public_key(&self) -> HashOut<F> {
+        self.tree.cap.0[0]
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.tree.leaves.len() - self.next_leaf
+    }
+
+    pub fn sign(&mut self, msg: HashOut<F>) -> Result<XmssSignature<F>> {
+        ensure!(self.remaining() > 0, "all one-time keys are used");
+        let leaf_index = self.next_leaf;
+        self.next_leaf += 1;
+        Ok(XmssSignature {
+            leaf_index,
+            wots: WotsSecretKey::from_seed(self.seed, leaf_index).sign(msg),
+            merkle_proof: self.tree.prove(leaf_index),
+        })
+    }
+}
+
+pub fn verify<F: RichField>(msg: HashOut<F>, sig: &XmssSignature<F>, root: HashOut<F>) -> bool {
+    if sig.wots.0.len() != NUM_CHAINS {
+        return false;
+    }
+    let leaf = public_key_from_signature(msg, &sig.wots);
+    verify_merkle_proof(
+        leaf.elements.to_vec(),
+        sig.leaf_index,
+        root,
+        &sig.merkle_proof,
+    )
+    .is_ok()
+}
+
+// circuit
+
+pub struct XmssTargets {
+    pub msg: HashOutTarget,
+    pub wots: Vec<HashOutTarget>,
+    pub merkle_proof: MerkleProofTargets,
+    pub root: HashOutTarget,
+}
+
+pub fn xmss_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    height: usize,
+) -> XmssTargets {
+    let msg = builder.add_virtual_hash();
+    let wots = builder.add_virtual_hashes(NUM_CHAINS);
+    let merkle_proof = add_virtual_merkle_proof(builder, height);
+
+    let leaf = public_key_from_signature_circuit(builder, msg, &wots);
+    let root = merkle_root_circuit::<PoseidonHash, F, D>(builder, leaf, &merkle_proof);
+
+    builder.register_public_inputs(&msg.elements);
+    builder.register_public_inputs(&root.elements);
+
+    XmssTargets {
+        msg,
+        wots,
+        merkle_proof,
+        root,
+    }
+}
+
+pub struct XmssCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub targets: XmssTargets,
+    pub data: CircuitData<F, C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    XmssCircuit<F, C, D>
+{
+    pub fn new(height: usize, proof_mode: ProofMode) -> Self {
+        let config = proof_mode.config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = xmss_circuit(&mut builder, height);
+        let data = builder.build::<C>();
+        XmssCircuit { targets, data }
+    }
+
+    pub fn prove(
+        &self,
+        msg: HashOut<F>,
+        sig: &XmssSignature<F>,
+        root: HashOut<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            sig.merkle_proof.siblings.len() == self.targets.merkle_proof.siblings.len(),
+            "signature is for a tree of another height"
+        );
+        ensure!(verify(msg, sig, root), "invalid signature");
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(self.targets.msg, msg);
+        set_wots_signature(&mut pw, &self.targets.wots, &sig.wots);
+        set_merkle_proof_witness(
+            &mut pw,
+            &self.targets.merkle_proof,
+            sig.leaf_index,
+            &sig.merkle_proof,
+        );
+        self.data.prove(pw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const HEIGHT: usize = 2;
+
+    fn signer() -> XmssSigner<F> {
+        XmssSigner::new(HashOut::from_partial(&[F::ONE, F::TWO]), HEIGHT)
+    }
+
+    #[test]
+    fn sign_and_verify() -> Result<()> {
+        let mut signer = signer();
+        let root = signer.public_key();
+        for i in 0..1 << HEIGHT {
+            let msg = HashOut::from_partial(&[F::from_canonical_usize(i)]);
+            let sig = signer.sign(msg)?;
+            assert_eq!(sig.leaf_index, i);
+            assert!(verify(msg, &sig, root));
+            let other = HashOut::from_partial(&[F::ONE, F::ONE]);
+            assert!(!verify(other, &sig, root));
+        }
+        assert_eq!(signer.remaining(), 0);
+        assert!(signer.sign(HashOut::from_partial(&[F::ONE])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_reused_leaf() -> Result<()> {
+        let mut signer = signer();
+        let root = signer.public_key();
+        let msg = HashOut::from_partial(&[F::ONE]);
+        let other = HashOut::from_partial(&[F::TWO]);
+        let first = signer.sign(msg)?;
+        // the signer never signs twice with one key
+        let second = signer.sign(other)?;
+        assert_ne!(first.leaf_index, second.leaf_index);
+
+        // a second message under the first leaf's path does not verify
+        let reused = XmssSignature {
+            wots: second.wots.clone(),
+            ..first.clone()
+        };
+        assert!(!verify(other, &reused, root));
+        // nor does a signature moved to another leaf
+        let moved = XmssSignature {
+            leaf_index: second.leaf_index,
+            ..first
+        };
+        assert!(!verify(msg, &moved, root));
+        Ok(())
+    }
+
+    #[test]
+    fn circuit() -> Result<()> {
+        let mut signer = signer();
+        let root = signer.public_key();
+        let msg = HashOut::from_partial(&[F::ONE]);
+        signer.sign(HashOut::from_partial(&[F::TWO]))?;
+        let sig = signer.sign(msg)?;
+
+        let circuit = XmssCircuit::<F, C, D>::new(HEIGHT, ProofMode::Standard);
+        let proof = circuit.prove(msg, &sig, root)?;
+        assert_eq!(proof.public_inputs, [msg.elements, root.elements].concat());
+        circuit.data.verify(proof)?;
+
+        assert!(circuit
+            .prove(HashOut::from_partial(&[F::TWO]), &sig, root)
+            .is_err());
+        let short = XmssCircuit::<F, C, D>::new(HEIGHT - 1, ProofMode::Standard);
+        assert!(short.prove(msg, &sig, root).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    plonk::circuit_data::{CircuitConfig, CommonCircuitData},
+};
+
+// Whether a circuit's proofs hide its private witness. The standard configs have
+// `zero_knowledge: false`: the proof is succinct, but FRI openings leak information
+// about the witness. With `zero_knowledge: true` the prover blinds the witness
+// polynomials and salts the Merkle leaves.
+//
+// The mode is part of `CommonCircuitData::config`, so anyone holding the verifier data
+// knows what kind of proof they received; a proof made in the other mode has a different
+// shape and does not verify. In a recursive tree only the outermost proof is published,
+// so inner layers can stay in standard mode as long as the final one blinds.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofMode {
+    Standard,
+    ZeroKnowledge,
+}
+
+impl ProofMode {
+    /// `standard_recursion_config` in this mode.
+    pub fn config(self) -> CircuitConfig {
+        self.apply(CircuitConfig::standard_recursion_config())
+    }
+
+    /// `standard_ecc_config` in this mode, for non-native arithmetic.
+    pub fn ecc_config(self) -> CircuitConfig {
+        self.apply(CircuitConfig::standard_ecc_config())
+    }
+
+    pub fn apply(self, config: CircuitConfig) -> CircuitConfig {
+        CircuitConfig {
+            zero_knowledge: self == ProofMode::ZeroKnowledge,
+            ..config
+        }
+    }
+
+    /// The mode recorded in a circuit's common data.
+    pub fn of<F: RichField + Extendable<D>, const D: usize>(common: &CommonCircuitData<F, D>) -> Self {
+        if common.config.zero_knowledge {
+            ProofMode::ZeroKnowledge
+        } else {
+            ProofMode::Standard
+        }
+    }
+
+    /// `--zk` on the command line selects zero knowledge, as in
+    /// `cargo run --example sudoku --release -- --zk`.
+    pub fn from_args() -> Self {
+        if std::env::args().any(|arg| arg == "--zk") {
+            ProofMode::ZeroKnowledge
+        } else {
+            ProofMode::Standard
+        }
+    }
+}
+
+impl fmt::Display for ProofMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProofMode::Standard => write!(f, "standard"),
+            ProofMode::ZeroKnowledge => write!(f, "zero-knowledge"),
+        }
+    }
+}
+
+/// How the signer's public key appears in the public inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublicKeyMode {
+    /// The key is public, as 32-bit limbs.
+    Exposed,
+    /// Only the Poseidon commitment H(key, blinding) is public.
+    Committed,
+}